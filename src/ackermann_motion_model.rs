@@ -0,0 +1,154 @@
+use crate::base;
+use crate::base::MotionIncrement;
+use crate::odometry_motion_model::OdometryModel;
+
+
+/// A car like robot with ackermann steering , the pose is the center of the rear axle
+/// The readings are the DISTANCE covered by the rear axle center (the mean of the two rear wheels)
+/// and the steering angle of a virtual wheel at the center of the front axle, positive to the left.
+/// Like [OdometryModel] the rear distance is cumulative, the model keeps the previous reading.
+/// The rear axle center moves on an arc of radius wheelbase/tan(steering)
+#[derive(Clone)]
+pub struct AckermannModel<T:base::Real=f32>{
+    odometry_rear:T,
+    pub x_t:base::Model2D<T>,
+    wheelbase:T
+}
+
+impl<T:base::Real> AckermannModel<T>{
+
+    pub fn new(wheelbase:T)->AckermannModel<T>{
+        AckermannModel{
+            odometry_rear:T::zero(),
+            x_t:base::Model2D::new(T::zero(),T::zero(),T::zero()),
+            wheelbase
+        }
+    }
+
+
+    /// Curvature of the path of the rear axle center for the given steering angle
+    pub fn curvature(&self, steering:T)->T{
+        steering.tan()/self.wheelbase
+    }
+
+
+    /// Returns radius of turning , angle of turn and distance travelled since the last stored
+    /// reading. The car can not turn in place , without steering it drives straight
+    /// This function does not affect any value of the state of the model
+    pub fn update_get_radius_angle_distance(&self, odometry_rear:T, steering:T)->MotionIncrement<T>{
+        let delta_s = odometry_rear - self.odometry_rear;
+        let curvature = self.curvature(steering);
+        // the turn moves the front axle sideways
+        MotionIncrement::new(delta_s*curvature,delta_s,self.wheelbase)
+    }
+
+
+    pub fn update_odometry_readings(&mut self, odometry_rear:T){
+        self.odometry_rear = odometry_rear;
+    }
+
+
+    /// Gets the jacobian of the new coordinates with respect to (delta_s, steering)
+    /// The arc is differentiated through its coefficients , not the radius , so the jacobian stays
+    /// accurate as the curvature goes to zero
+    pub fn update_get_control_jacobian_stateless(&self, state:base::Model2D<T>, odometry_rear:T, steering:T)->base::ControlJacobianModel2D<T>{
+        let delta_s = odometry_rear - self.odometry_rear;
+        let curvature = self.curvature(steering);
+        // columns for (forward, left, rotation) of the rear axle center , the rotation is delta_s*k
+        let motion = base::integrate_body_motion_jacobian(state,delta_s,T::zero(),delta_s*curvature);
+
+        // d curvature / d steering = 1/(L cos^2(steering))
+        let cos_steering = steering.cos();
+        let dk = T::one()/(self.wheelbase*cos_steering*cos_steering);
+        let mut data = base::ControlJacobianModel2D::zeros();
+        for (row,motion_row) in data.data.iter_mut().zip(motion.iter()){
+            row[0] = motion_row[0] + curvature*motion_row[2];
+            row[1] = delta_s*dk*motion_row[2];
+        }
+        data
+    }
+}
+
+
+/// `odom_l` is the cumulative rear axle distance and `odom_r` the steering angle. The control
+/// jacobian is with respect to (rear distance increment, steering angle)
+impl<T:base::Real> base::MotionUpdate2D<T> for AckermannModel<T>{
+
+    fn update_coords_odometry(&mut self, odom_l:T, odom_r:T)->base::Model2D<T>{
+        self.x_t = base::MotionUpdate2D::update_coords_odometry_stateless(self,self.x_t,odom_l,odom_r);
+        self.x_t
+    }
+
+    fn update_coords_odometry_stateless(&mut self, pos:base::Model2D<T>, odom_l:T, odom_r:T)->base::Model2D<T>{
+        let pos = OdometryModel::update_position_coords_stateless(pos,self.update_get_radius_angle_distance(odom_l,odom_r).change());
+        self.update_odometry_readings(odom_l);
+        pos
+    }
+
+    fn get_jacobian_stateless(&mut self, pos:base::Model2D<T>, odom_l:T, odom_r:T)->base::JacobianModel2D<T>{
+        OdometryModel::update_get_jacobian_stateless(pos,self.update_get_radius_angle_distance(odom_l,odom_r).change())
+    }
+
+    fn get_control_jacobian_stateless(&mut self, pos:base::Model2D<T>, odom_l:T, odom_r:T)->base::ControlJacobianModel2D<T>{
+        self.update_get_control_jacobian_stateless(pos,odom_l,odom_r)
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::base::MotionUpdate2D;
+
+    #[test]
+    fn ackermann_circle_test(){
+        // steering so that the radius is exactly 2 , a full circle brings the car back
+        let wheelbase = 0.5f64;
+        let steering = (wheelbase/2.0).atan();
+        let mut model = super::AckermannModel::new(wheelbase);
+        let circumference = 2.0*std::f64::consts::PI*2.0;
+        let mut pos = model.x_t;
+        for i in 1..=8{
+            pos = model.update_coords_odometry(circumference*i as f64/8.0,steering);
+            if i==2{
+                // a quarter circle to the left
+                assert!((pos.x-2.0).abs()<1e-9 && (pos.y-2.0).abs()<1e-9);
+            }
+        }
+        assert!(pos.x.abs()<1e-9 && pos.y.abs()<1e-9);
+        assert!((pos.theta-2.0*std::f64::consts::PI).abs()<1e-9);
+
+        // no steering drives straight
+        let pos = model.update_coords_odometry_stateless(crate::base::Model2D::new(0.0,0.0,0.5),circumference+1.0,0.0);
+        assert!((pos.x-0.5f64.cos()).abs()<1e-12 && (pos.y-0.5f64.sin()).abs()<1e-12);
+    }
+
+    #[test]
+    fn control_jacobian_ackermann_model_test(){
+        let state = crate::base::Model2D::new(0.3f64,-0.2,0.7);
+        let h = 1e-6;
+        for (distance,steering) in [(0.4,0.3),(0.4,0.0),(0.4,1e-6),(0.4,-1e-9),(-0.3,-0.5)].iter(){
+            let mut model = super::AckermannModel::new(0.5);
+            model.update_odometry_readings(1.0);
+            let jacobian = model.get_control_jacobian_stateless(state,1.0+distance,*steering);
+            let update = |u:[f64;2]| model.clone().update_coords_odometry_stateless(state,1.0+u[0],u[1]);
+            crate::tests::assert_numeric_jacobian(&jacobian.data,update,[*distance,*steering],h,1e-8);
+        }
+    }
+
+    #[test]
+    fn ackermann_ekf_test(){
+        // plugs into the filters like the differential drive models
+        let mut model = super::AckermannModel::new(0.5);
+        let mut ekf = crate::ekf::ExtendedKalmanFilter::new(
+            crate::base::Model2D::new(0.0,0.0,0.0),
+            crate::base::Covariance2D::diagonal(1e-4,1e-4,1e-4));
+        let control_noise = crate::base::ControlCovariance2D::diagonal(1e-4,1e-3);
+        for i in 1..=10{
+            ekf.predict(&mut model,0.1*i as f32,0.2,&control_noise);
+        }
+        assert!(ekf.mean.theta>0.0);
+        assert!(ekf.covariance.get(1,1)>1e-4);
+    }
+}
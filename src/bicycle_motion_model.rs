@@ -0,0 +1,232 @@
+use crate::base;
+
+
+/// Point of the vehicle whose pose the [BicycleModel] tracks
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum ReferencePoint<T:base::Real=f32>{
+    RearAxle,
+    /// The center of gravity , at the given distance in front of the rear axle
+    CenterOfGravity(T),
+    FrontAxle
+}
+
+
+/// Kinematic bicycle model , the two wheels of each axle are lumped into one.
+/// The inputs are the velocity of the reference point and the steering angle of the front wheel
+/// (positive to the left) , held constant over one time step.
+/// The velocity of the reference point makes the slip angle beta with the heading of the vehicle,
+/// zero at the rear axle and equal to the steering angle at the front axle.
+/// With constant inputs the reference point moves on a circle , which is integrated exactly.
+#[derive(Clone)]
+pub struct BicycleModel<T:base::Real=f32>{
+    pub x_t:base::Model2D<T>,
+    time_step:T,
+    wheelbase:T,
+    reference:ReferencePoint<T>
+}
+
+impl<T:base::Real> BicycleModel<T>{
+    /// The reference point is the center of the rear axle
+    pub fn new(wheelbase:T, time_step:T)->BicycleModel<T>{
+        Self::with_reference_point(wheelbase,time_step,ReferencePoint::RearAxle)
+    }
+
+    pub fn with_reference_point(wheelbase:T, time_step:T, reference:ReferencePoint<T>)->BicycleModel<T>{
+        BicycleModel{
+            x_t:base::Model2D::new(T::zero(),T::zero(),T::zero()),
+            time_step,
+            wheelbase,
+            reference
+        }
+    }
+
+
+    /// Distance of the reference point in front of the rear axle
+    fn reference_distance(&self)->T{
+        match self.reference{
+            ReferencePoint::RearAxle=>T::zero(),
+            ReferencePoint::CenterOfGravity(distance)=>distance,
+            ReferencePoint::FrontAxle=>self.wheelbase
+        }
+    }
+
+
+    /// Slip angle beta between the heading and the velocity of the reference point
+    pub fn slip_angle(&self, steering:T)->T{
+        (self.reference_distance()/self.wheelbase*steering.tan()).atan()
+    }
+
+
+    /// Heading change per distance travelled by the reference point , cos(beta)*tan(steering)/L
+    pub fn curvature(&self, steering:T)->T{
+        self.slip_angle(steering).cos()*steering.tan()/self.wheelbase
+    }
+
+
+    /// Rate of change of the heading
+    pub fn yaw_rate(&self, v:T, steering:T)->T{
+        v*self.curvature(steering)
+    }
+
+
+    /// Displacement (forward, left, rotation) of the reference point in the frame of the vehicle
+    /// over one time step , the velocity makes the angle beta with the heading
+    fn body_motion(&self, v:T, steering:T)->(T,T,T){
+        let distance = v*self.time_step;
+        let (sin_b,cos_b) = self.slip_angle(steering).sin_cos();
+        (distance*cos_b, distance*sin_b, distance*self.curvature(steering))
+    }
+
+
+    /// The arc is integrated from its length and angle , not the radius , so the pose stays
+    /// accurate for tiny steering angles
+    pub fn update_coords_stateless(&self, state:base::Model2D<T>, v:T, steering:T)->base::Model2D<T>{
+        let (forward,left,rotation) = self.body_motion(v,steering);
+        base::integrate_body_motion(state,forward,left,rotation)
+    }
+
+
+    /// Gets the jacobian of the new coordinates with respect to (x, y, theta)
+    pub fn update_get_jacobian_stateless(&self, state:base::Model2D<T>, v:T, steering:T)->base::JacobianModel2D<T>{
+        let new = self.update_coords_stateless(state,v,steering);
+        let mut data = base::JacobianModel2D::identity();
+        let _ = data.column(2,(state.y - new.y, new.x - state.x, T::one()));
+        data
+    }
+
+
+    /// Gets the jacobian of the new coordinates with respect to (v, steering)
+    /// Chain rule through the body displacement , continuous as the steering goes to zero
+    pub fn update_get_control_jacobian_stateless(&self, state:base::Model2D<T>, v:T, steering:T)->base::ControlJacobianModel2D<T>{
+        let dt = self.time_step;
+        let distance = v*dt;
+        let beta = self.slip_angle(steering);
+        let (sin_b,cos_b) = beta.sin_cos();
+        let curvature = self.curvature(steering);
+        let (forward,left,rotation) = self.body_motion(v,steering);
+        let motion = base::integrate_body_motion_jacobian(state,forward,left,rotation);
+
+        // d beta / d steering and d curvature / d steering
+        let ratio = self.reference_distance()/self.wheelbase;
+        let (tan_s,cos_s) = (steering.tan(),steering.cos());
+        let sec2 = T::one()/(cos_s*cos_s);
+        let dbeta = ratio*sec2/(T::one() + ratio*ratio*tan_s*tan_s);
+        let dk = (-sin_b*dbeta*tan_s + cos_b*sec2)/self.wheelbase;
+
+        // columns (forward, left, rotation) for v and for the steering angle
+        let body = [
+            [dt*cos_b, dt*sin_b, dt*curvature],
+            [-distance*sin_b*dbeta, distance*cos_b*dbeta, distance*dk]
+        ];
+        let mut data = base::ControlJacobianModel2D::zeros();
+        for (row,motion_row) in data.data.iter_mut().zip(motion.iter()){
+            for (value,column) in row.iter_mut().zip(body.iter()){
+                *value = motion_row.iter().zip(column.iter()).fold(T::zero(),|sum,(a,b)| sum + *a**b);
+            }
+        }
+        data
+    }
+}
+
+
+/// `odom_l` is the velocity of the reference point and `odom_r` the steering angle over one time
+/// step , the model does not keep track of previous readings
+impl<T:base::Real> base::MotionUpdate2D<T> for BicycleModel<T>{
+
+    fn update_coords_odometry(&mut self, odom_l:T, odom_r:T)->base::Model2D<T>{
+        self.x_t = self.update_coords_stateless(self.x_t,odom_l,odom_r);
+        self.x_t
+    }
+
+    fn update_coords_odometry_stateless(&mut self, pos:base::Model2D<T>, odom_l:T, odom_r:T)->base::Model2D<T>{
+        self.update_coords_stateless(pos,odom_l,odom_r)
+    }
+
+    fn get_jacobian_stateless(&mut self, pos:base::Model2D<T>, odom_l:T, odom_r:T)->base::JacobianModel2D<T>{
+        self.update_get_jacobian_stateless(pos,odom_l,odom_r)
+    }
+
+    fn get_control_jacobian_stateless(&mut self, pos:base::Model2D<T>, odom_l:T, odom_r:T)->base::ControlJacobianModel2D<T>{
+        self.update_get_control_jacobian_stateless(pos,odom_l,odom_r)
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::{BicycleModel,ReferencePoint};
+
+    #[test]
+    fn reference_points_test(){
+        // the rear and front axle of the same vehicle stay one wheelbase apart
+        let (wheelbase,dt) = (2.5f64,0.1);
+        let rear = BicycleModel::with_reference_point(wheelbase,dt,ReferencePoint::RearAxle);
+        let front = BicycleModel::with_reference_point(wheelbase,dt,ReferencePoint::FrontAxle);
+        let cg = BicycleModel::with_reference_point(wheelbase,dt,ReferencePoint::CenterOfGravity(1.0));
+        assert_eq!(rear.slip_angle(0.3),0.0);
+        assert!((front.slip_angle(0.3)-0.3).abs()<1e-12);
+
+        let steering = 0.3;
+        let v_rear = 5.0;
+        // all points of the body turn at the same rate , so each speed is the rear speed scaled
+        let v_front = v_rear/front.slip_angle(steering).cos();
+        let v_cg = v_rear/cg.slip_angle(steering).cos();
+        let (mut p_rear,mut p_front,mut p_cg) = (crate::base::Model2D::new(0.0,0.0,0.2),crate::base::Model2D::new(wheelbase*0.2f64.cos(),wheelbase*0.2f64.sin(),0.2),crate::base::Model2D::new(0.2f64.cos(),0.2f64.sin(),0.2));
+        for _ in 0..50{
+            p_rear = rear.update_coords_stateless(p_rear,v_rear,steering);
+            p_front = front.update_coords_stateless(p_front,v_front,steering);
+            p_cg = cg.update_coords_stateless(p_cg,v_cg,steering);
+        }
+        assert!((p_rear.theta-p_front.theta).abs()<1e-9 && (p_rear.theta-p_cg.theta).abs()<1e-9);
+        assert!((p_front.x-(p_rear.x+wheelbase*p_rear.theta.cos())).abs()<1e-9);
+        assert!((p_front.y-(p_rear.y+wheelbase*p_rear.theta.sin())).abs()<1e-9);
+        assert!((p_cg.x-(p_rear.x+p_rear.theta.cos())).abs()<1e-9);
+        assert!((p_cg.y-(p_rear.y+p_rear.theta.sin())).abs()<1e-9);
+    }
+
+    #[test]
+    fn jacobians_bicycle_model_test(){
+        let model = BicycleModel::with_reference_point(2.5f64,0.1,ReferencePoint::CenterOfGravity(1.2));
+        let state = crate::base::Model2D::new(1.0,-2.0,0.4);
+        let h = 1e-5;
+        for (v,steering) in [(4.0,0.2),(4.0,0.0),(-2.0,-0.4)].iter(){
+            let g = model.update_get_jacobian_stateless(state,*v,*steering);
+            let update = |p:[f64;3]| model.update_coords_stateless(crate::base::Model2D::new(p[0],p[1],p[2]),*v,*steering);
+            crate::tests::assert_numeric_jacobian(&g.data,update,[state.x,state.y,state.theta],h,1e-6);
+
+            let jacobian = model.update_get_control_jacobian_stateless(state,*v,*steering);
+            let update = |u:[f64;2]| model.update_coords_stateless(state,u[0],u[1]);
+            crate::tests::assert_numeric_jacobian(&jacobian.data,update,[*v,*steering],h,1e-5);
+        }
+    }
+
+    #[test]
+    fn small_steering_bicycle_model_test(){
+        // a steering angle of 1e-6 , the radius of the arc is millions of meters
+        let (v,dt) = (4.0f64,0.1);
+        let rear = BicycleModel::new(2.5f64,dt);
+        let state = crate::base::Model2D::new(1.0,-2.0,0.4);
+        let steering = 1e-6;
+        let pos = rear.update_coords_stateless(state,v,steering);
+        let (s,alpha) = (v*dt,v*dt*steering.tan()/2.5);
+        let (forward,left) = (s*(1.0 - alpha*alpha/6.0), s*alpha/2.0);
+        assert!((pos.x-(state.x + forward*state.theta.cos() - left*state.theta.sin())).abs()<1e-15);
+        assert!((pos.y-(state.y + forward*state.theta.sin() + left*state.theta.cos())).abs()<1e-15);
+        assert!((pos.theta-state.theta-alpha).abs()<1e-15);
+
+        // the jacobians against central differences , continuous through the straight line
+        let model = BicycleModel::with_reference_point(2.5f64,dt,ReferencePoint::CenterOfGravity(1.2));
+        let h = 1e-6;
+        for steering in [1e-6,-1e-7,0.0].iter(){
+            let g = model.update_get_jacobian_stateless(state,v,*steering);
+            let update = |p:[f64;3]| model.update_coords_stateless(crate::base::Model2D::new(p[0],p[1],p[2]),v,*steering);
+            crate::tests::assert_numeric_jacobian(&g.data,update,[state.x,state.y,state.theta],h,1e-8);
+
+            let jacobian = model.update_get_control_jacobian_stateless(state,v,*steering);
+            let update = |u:[f64;2]| model.update_coords_stateless(state,u[0],u[1]);
+            crate::tests::assert_numeric_jacobian(&jacobian.data,update,[v,*steering],h,1e-8);
+        }
+    }
+}
@@ -0,0 +1,568 @@
+//! Systematic odometry calibration from ground truth
+//! Estimates the effective base length and the left and right wheel radii of a differential drive
+//! robot by nonlinear least squares (levenberg marquardt) on the position errors of the dead
+//! reckoned trajectory. The ground truth has no heading , so the initial heading is estimated as
+//! well. The optimization is done in double precision whatever the scalar type of the inputs.
+//! [calibrate_skid_steer] estimates the ICRs of a tracked vehicle the same way.
+//! [umbmark] implements Borenstein's square path procedure for when there is no ground truth
+//! trajectory , only the measured stop positions.
+use crate::base;
+use crate::eval::ErrorStatistics;
+use crate::odometry_motion_model::OdometryModel;
+use crate::skid_steer_motion_model::SkidSteerModel;
+
+
+/// Number of estimated parameters , three of the robot and the initial heading
+const PARAMETERS:usize = 4;
+
+
+/// Result of [calibrate_odometry]
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub struct OdometryCalibration<T:base::Real=f32>{
+    pub base_length:T,
+    pub radius_left:T,
+    pub radius_right:T,
+    pub initial_heading:T,
+    /// Covariance of (base_length, radius_left, radius_right, initial_heading) , the inverse of
+    /// the gauss newton hessian scaled by the residual variance
+    pub covariance:[[T;PARAMETERS];PARAMETERS],
+    /// Distances between the calibrated trajectory and the ground truth
+    pub residuals:ErrorStatistics<T>,
+    pub iterations:usize,
+    pub converged:bool
+}
+
+impl<T:base::Real> OdometryCalibration<T>{
+    /// Standard deviations of (base_length, radius_left, radius_right, initial_heading)
+    pub fn standard_deviations(&self)->[T;PARAMETERS]{
+        standard_deviations(&self.covariance)
+    }
+
+    /// Converts wheel angles to the distances the [OdometryModel] takes
+    pub fn wheel_distances(&self, angle_l:T, angle_r:T)->(T,T){
+        (angle_l*self.radius_left,angle_r*self.radius_right)
+    }
+
+    /// An odometry model with the calibrated base length
+    pub fn model(&self)->OdometryModel<T>{
+        OdometryModel::new(self.base_length)
+    }
+}
+
+
+
+/// Result of [calibrate_skid_steer]
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub struct SkidSteerCalibration<T:base::Real=f32>{
+    pub icr_left:T,
+    pub icr_right:T,
+    pub icr_x:T,
+    pub initial_heading:T,
+    /// Covariance of (icr_left, icr_right, icr_x, initial_heading)
+    pub covariance:[[T;PARAMETERS];PARAMETERS],
+    /// Distances between the calibrated trajectory and the ground truth
+    pub residuals:ErrorStatistics<T>,
+    pub iterations:usize,
+    pub converged:bool
+}
+
+impl<T:base::Real> SkidSteerCalibration<T>{
+    /// Standard deviations of (icr_left, icr_right, icr_x, initial_heading)
+    pub fn standard_deviations(&self)->[T;PARAMETERS]{
+        standard_deviations(&self.covariance)
+    }
+
+    /// A skid steer model with the calibrated ICRs
+    pub fn model(&self)->SkidSteerModel<T>{
+        SkidSteerModel::new(self.icr_left,self.icr_right,self.icr_x)
+    }
+}
+
+
+fn standard_deviations<T:base::Real>(covariance:&[[T;PARAMETERS];PARAMETERS])->[T;PARAMETERS]{
+    let mut out = [T::zero();PARAMETERS];
+    for (i,value) in out.iter_mut().enumerate(){
+        *value = covariance[i][i].max(T::zero()).sqrt();
+    }
+    out
+}
+
+
+
+/// Position errors (x, y interleaved) of the trajectory dead reckoned by `model` from the
+/// cumulative `readings`. The model must already hold the first readings , the trajectory starts
+/// at the first true position with the given heading
+fn trajectory_residuals<M:base::MotionUpdate2D<f64>>(mut model:M, readings:&[(f64,f64)], truth:&[(f64,f64)], heading:f64)->Vec<f64>{
+    let mut pose = base::Model2D::new(truth[0].0,truth[0].1,heading);
+    let mut out = Vec::with_capacity(2*truth.len());
+    for ((l,r),(x,y)) in readings.iter().zip(truth.iter()){
+        pose = model.update_coords_odometry_stateless(pose,*l,*r);
+        out.push(pose.x - x);
+        out.push(pose.y - y);
+    }
+    out
+}
+
+
+fn sum_squares(residuals:&[f64])->f64{
+    residuals.iter().map(|r| r*r).sum()
+}
+
+
+/// Forward difference jacobian of the residuals , one column per parameter
+fn residual_jacobian<F:Fn(&[f64;PARAMETERS])->Vec<f64>>(residual_function:&F, params:&[f64;PARAMETERS], residuals:&[f64])->Vec<[f64;PARAMETERS]>{
+    let mut jacobian = vec![[0.0;PARAMETERS];residuals.len()];
+    for col in 0..PARAMETERS{
+        let step = 1e-7*params[col].abs().max(1e-3);
+        let mut shifted = *params;
+        shifted[col] += step;
+        let shifted_residuals = residual_function(&shifted);
+        for (row,(shifted_r,r)) in jacobian.iter_mut().zip(shifted_residuals.iter().zip(residuals.iter())){
+            row[col] = (shifted_r - r)/step;
+        }
+    }
+    jacobian
+}
+
+
+/// J^T*J and J^T*r
+fn normal_equations(jacobian:&[[f64;PARAMETERS]], residuals:&[f64])->([[f64;PARAMETERS];PARAMETERS],[f64;PARAMETERS]){
+    let mut jtj = [[0.0;PARAMETERS];PARAMETERS];
+    let mut jtr = [0.0;PARAMETERS];
+    for (row,r) in jacobian.iter().zip(residuals.iter()){
+        for i in 0..PARAMETERS{
+            jtr[i] += row[i]*r;
+            for j in 0..PARAMETERS{
+                jtj[i][j] += row[i]*row[j];
+            }
+        }
+    }
+    (jtj,jtr)
+}
+
+
+/// Result of [least_squares] , the covariance is already scaled by the residual variance
+struct LeastSquaresFit{
+    params:[f64;PARAMETERS],
+    residuals:Vec<f64>,
+    covariance:[[f64;PARAMETERS];PARAMETERS],
+    iterations:usize,
+    converged:bool
+}
+
+impl LeastSquaresFit{
+    fn covariance<T:base::Real>(&self)->[[T;PARAMETERS];PARAMETERS]{
+        let mut out = [[T::zero();PARAMETERS];PARAMETERS];
+        for (row,fit_row) in out.iter_mut().zip(self.covariance.iter()){
+            for (value,fit_value) in row.iter_mut().zip(fit_row.iter()){
+                *value = base::real(*fit_value);
+            }
+        }
+        out
+    }
+
+    /// Statistics of the position errors , the residuals are (x, y) pairs
+    fn position_errors<T:base::Real>(&self)->Option<ErrorStatistics<T>>{
+        let distances:Vec<T> = self.residuals.chunks(2).map(|r| base::real((r[0]*r[0] + r[1]*r[1]).sqrt())).collect();
+        ErrorStatistics::from_errors(&distances)
+    }
+}
+
+
+/// Levenberg marquardt minimization of the sum of squares of `residual_function`.
+/// Returns None if the parameters are not observable at the minimum
+fn least_squares<F:Fn(&[f64;PARAMETERS])->Vec<f64>>(residual_function:F, initial:[f64;PARAMETERS], max_iterations:usize)->Option<LeastSquaresFit>{
+    let mut params = initial;
+    let mut residuals = residual_function(&params);
+    let mut cost = sum_squares(&residuals);
+    let mut lambda = 1e-3;
+    let mut iterations = 0;
+    let mut converged = false;
+
+    while iterations < max_iterations && !converged{
+        iterations += 1;
+        let jacobian = residual_jacobian(&residual_function,&params,&residuals);
+        let (jtj,jtr) = normal_equations(&jacobian,&residuals);
+
+        // raise the damping until the step lowers the cost
+        loop{
+            let mut damped = jtj;
+            for (i,row) in damped.iter_mut().enumerate(){
+                row[i] += lambda*jtj[i][i];
+            }
+            let inverse = crate::ekf::invert(damped);
+            let candidate = inverse.map(|inverse|{
+                let mut candidate = params;
+                for (value,row) in candidate.iter_mut().zip(inverse.iter()){
+                    *value -= row.iter().zip(jtr.iter()).map(|(a,b)| a*b).sum::<f64>();
+                }
+                candidate
+            });
+            if let Some(candidate) = candidate{
+                let candidate_residuals = residual_function(&candidate);
+                let candidate_cost = sum_squares(&candidate_residuals);
+                if candidate_cost.is_finite() && candidate_cost <= cost{
+                    converged = cost - candidate_cost <= 1e-12*cost;
+                    params = candidate;
+                    residuals = candidate_residuals;
+                    cost = candidate_cost;
+                    lambda = (lambda/10.0).max(1e-12);
+                    break
+                }
+            }
+            lambda *= 10.0;
+            if lambda > 1e12{
+                // no step lowers the cost any more , this is a minimum
+                converged = true;
+                break
+            }
+        }
+    }
+
+    let jacobian = residual_jacobian(&residual_function,&params,&residuals);
+    let (jtj,_) = normal_equations(&jacobian,&residuals);
+    let inverse = crate::ekf::invert(jtj)?;
+    let variance = cost/(residuals.len() - PARAMETERS) as f64;
+    let mut covariance = inverse;
+    covariance.iter_mut().for_each(|row| row.iter_mut().for_each(|value| *value *= variance));
+    Some(LeastSquaresFit{
+        params,
+        residuals,
+        covariance,
+        iterations,
+        converged
+    })
+}
+
+
+/// Pairs of readings or positions in double precision
+type Log = Vec<(f64,f64)>;
+
+/// Converts a log and the ground truth to double precision , None if they do not match
+fn to_f64_logs<T:base::Real>(readings:&[(T,T)], truth:&[(T,T)])->Option<(Log,Log)>{
+    if readings.len()!=truth.len() || truth.len()<PARAMETERS{
+        return None
+    }
+    let to_f64 = |(a,b):&(T,T)| (a.to_f64().unwrap_or(f64::NAN),b.to_f64().unwrap_or(f64::NAN));
+    Some((readings.iter().map(to_f64).collect(),truth.iter().map(to_f64).collect()))
+}
+
+
+
+/// Calibrates the odometry from a log of cumulative wheel angles (radians, as in `ws_pos8008.txt`)
+/// and the true positions at the same instants (as in `abs_pos8008.txt`).
+/// `base_length` , `wheel_radius` and `initial_heading` are the starting point of the
+/// optimization, the nominal values of the robot are usually good enough.
+/// Returns None if the logs are of different length , too short , or the parameters are not
+/// observable (e.g. the robot never turns)
+pub fn calibrate_odometry<T:base::Real>(wheel_angles:&[(T,T)], truth:&[(T,T)], base_length:T, wheel_radius:T, initial_heading:T, max_iterations:usize)->Option<OdometryCalibration<T>>{
+    let (wheel_angles,truth) = to_f64_logs(wheel_angles,truth)?;
+    let residual_function = |params:&[f64;PARAMETERS]|{
+        let [base_length,radius_left,radius_right,heading] = *params;
+        let distances:Vec<(f64,f64)> = wheel_angles.iter().map(|(l,r)| (l*radius_left,r*radius_right)).collect();
+        let mut model = OdometryModel::new(base_length);
+        model.update_odometry_readings(distances[0].0,distances[0].1);
+        trajectory_residuals(model,&distances,&truth,heading)
+    };
+    let wheel_radius = wheel_radius.to_f64()?;
+    let initial = [base_length.to_f64()?,wheel_radius,wheel_radius,initial_heading.to_f64()?];
+    let fit = least_squares(residual_function,initial,max_iterations)?;
+
+    Some(OdometryCalibration{
+        base_length:base::real(fit.params[0]),
+        radius_left:base::real(fit.params[1]),
+        radius_right:base::real(fit.params[2]),
+        initial_heading:base::normalize_angle(base::real(fit.params[3])),
+        covariance:fit.covariance(),
+        residuals:fit.position_errors()?,
+        iterations:fit.iterations,
+        converged:fit.converged
+    })
+}
+
+
+/// Estimates the ICR parameters of a [SkidSteerModel] from a log of cumulative track distances and
+/// the true positions at the same instants. The optimization starts from the ICRs of an ideal
+/// differential drive of `base_length` and the given heading.
+/// Returns None if the logs are of different length , too short , or the parameters are not
+/// observable (the vehicle has to turn for the ICRs to show)
+pub fn calibrate_skid_steer<T:base::Real>(track_distances:&[(T,T)], truth:&[(T,T)], base_length:T, initial_heading:T, max_iterations:usize)->Option<SkidSteerCalibration<T>>{
+    let (track_distances,truth) = to_f64_logs(track_distances,truth)?;
+    let residual_function = |params:&[f64;PARAMETERS]|{
+        let [icr_left,icr_right,icr_x,heading] = *params;
+        let mut model = SkidSteerModel::new(icr_left,icr_right,icr_x);
+        model.update_odometry_readings(track_distances[0].0,track_distances[0].1);
+        trajectory_residuals(model,&track_distances,&truth,heading)
+    };
+    let half = base_length.to_f64()?/2.0;
+    let fit = least_squares(residual_function,[half,-half,0.0,initial_heading.to_f64()?],max_iterations)?;
+
+    Some(SkidSteerCalibration{
+        icr_left:base::real(fit.params[0]),
+        icr_right:base::real(fit.params[1]),
+        icr_x:base::real(fit.params[2]),
+        initial_heading:base::normalize_angle(base::real(fit.params[3])),
+        covariance:fit.covariance(),
+        residuals:fit.position_errors()?,
+        iterations:fit.iterations,
+        converged:fit.converged
+    })
+}
+
+
+
+
+/// A UMBmark run around a square , the cumulative wheel distances logged during the run (the first
+/// reading is the start) and the measured position where the robot actually stopped. The position
+/// is in the frame of the start pose , x pointing forward.
+#[derive(Clone,Debug)]
+pub struct SquareRun<T:base::Real=f32>{
+    pub readings:Vec<(T,T)>,
+    pub final_position:(T,T)
+}
+
+impl<T:base::Real> SquareRun<T>{
+    pub fn new(readings:Vec<(T,T)>, final_position:(T,T))->SquareRun<T>{
+        SquareRun{
+            readings,
+            final_position
+        }
+    }
+
+    /// Measured minus dead reckoned return position , None if there are no readings
+    pub fn return_position_error(&self, base_length:T)->Option<(T,T)>{
+        let (first_l,first_r) = *self.readings.first()?;
+        let mut model = OdometryModel::new(base_length);
+        model.update_odometry_readings(first_l,first_r);
+        let mut pose = base::Model2D::new(T::zero(),T::zero(),T::zero());
+        for (l,r) in self.readings.iter().skip(1){
+            pose = base::MotionUpdate2D::update_coords_odometry_stateless(&mut model,pose,*l,*r);
+        }
+        Some((self.final_position.0 - pose.x,self.final_position.1 - pose.y))
+    }
+}
+
+
+
+/// Result of [umbmark] (Borenstein and Feng , "Measurement and correction of systematic odometry
+/// errors in mobile robots"). Type A errors come from the wheelbase , type B errors from unequal
+/// wheel diameters.
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub struct UmbmarkCorrection<T:base::Real=f32>{
+    /// Center of gravity of the return position errors of the clockwise runs
+    pub center_cw:(T,T),
+    /// Center of gravity of the return position errors of the counter clockwise runs
+    pub center_ccw:(T,T),
+    /// Orientation error of every 90 degree turn caused by the wheelbase (type A) , radians
+    pub alpha:T,
+    /// Orientation error of every side caused by the wheel diameters (type B) , radians
+    pub beta:T,
+    /// Ratio of the right and left wheel diameters
+    pub diameter_ratio:T,
+    /// Ratio of the actual and nominal wheelbase
+    pub wheelbase_ratio:T,
+    /// Corrected base length for the [OdometryModel]
+    pub base_length:T,
+    /// Factors the left and right wheel distances are multiplied with
+    pub scale_left:T,
+    pub scale_right:T
+}
+
+impl<T:base::Real> UmbmarkCorrection<T>{
+    /// Borenstein's measure of the systematic odometry error , the larger distance of the two
+    /// centers of gravity from the start
+    pub fn max_systematic_error(&self)->T{
+        let distance = |c:(T,T)| (c.0*c.0 + c.1*c.1).sqrt();
+        distance(self.center_cw).max(distance(self.center_ccw))
+    }
+
+    /// Corrects wheel distances computed with the nominal wheel diameter
+    pub fn wheel_distances(&self, odometry_l:T, odometry_r:T)->(T,T){
+        (odometry_l*self.scale_left,odometry_r*self.scale_right)
+    }
+
+    /// An odometry model with the corrected base length , feed it [Self::wheel_distances]
+    pub fn model(&self)->OdometryModel<T>{
+        OdometryModel::new(self.base_length)
+    }
+}
+
+
+/// Mean return position error of a set of runs
+fn center_of_gravity<T:base::Real>(runs:&[SquareRun<T>], base_length:T)->Option<(T,T)>{
+    if runs.is_empty(){
+        return None
+    }
+    let (mut x,mut y) = (T::zero(),T::zero());
+    for run in runs.iter(){
+        let (ex,ey) = run.return_position_error(base_length)?;
+        x += ex;
+        y += ey;
+    }
+    let count = base::real::<T>(runs.len() as f64);
+    Some((x/count,y/count))
+}
+
+
+/// UMBmark calibration from runs around a square of side `side_length` , in the clockwise and the
+/// counter clockwise direction , with the nominal `base_length`. The correction is first order ,
+/// it assumes the errors are small compared to the square.
+/// Returns None if a direction has no runs or a run has no readings
+pub fn umbmark<T:base::Real>(cw:&[SquareRun<T>], ccw:&[SquareRun<T>], side_length:T, base_length:T)->Option<UmbmarkCorrection<T>>{
+    let center_cw = center_of_gravity(cw,base_length)?;
+    let center_ccw = center_of_gravity(ccw,base_length)?;
+    let two = base::real::<T>(2.0);
+    let four_l = base::real::<T>(4.0)*side_length;
+
+    let alpha = -(center_cw.0 + center_ccw.0)/four_l;
+    let beta = -(center_cw.0 - center_ccw.0)/four_l;
+    let wheelbase_ratio = T::FRAC_PI_2()/(T::FRAC_PI_2() - alpha);
+    // the radius of the arc each side becomes , equal wheels drive straight
+    let diameter_ratio = if beta==T::zero(){
+        T::one()
+    }else{
+        let radius = side_length/two/(beta/two).sin();
+        (radius + base_length/two)/(radius - base_length/two)
+    };
+
+    Some(UmbmarkCorrection{
+        center_cw,
+        center_ccw,
+        alpha,
+        beta,
+        diameter_ratio,
+        wheelbase_ratio,
+        base_length:base_length*wheelbase_ratio,
+        scale_left:two/(diameter_ratio + T::one()),
+        scale_right:two/(T::one()/diameter_ratio + T::one())
+    })
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::base::MotionUpdate2D;
+
+    #[test]
+    fn synthetic_calibration_test(){
+        // a robot with unequal wheels driving a wavy path
+        let (base_length,radius_left,radius_right,heading) = (0.11f64,0.0205,0.0212,0.4);
+        let mut model = crate::odometry_motion_model::OdometryModel::new(base_length);
+        let mut pose = crate::base::Model2D::new(0.5,-0.2,heading);
+        let (mut angle_l,mut angle_r) = (0.0,0.0);
+        let mut angles = vec![(0.0,0.0)];
+        let mut truth = vec![(pose.x,pose.y)];
+        for step in 0..400{
+            angle_l += 0.5 + 0.3*(step as f64*0.05).sin();
+            angle_r += 0.5 - 0.3*(step as f64*0.05).sin();
+            pose = model.update_coords_odometry_stateless(pose,angle_l*radius_left,angle_r*radius_right);
+            angles.push((angle_l,angle_r));
+            truth.push((pose.x,pose.y));
+        }
+
+        let calibration = super::calibrate_odometry(&angles,&truth,0.1,0.021,0.3,50).unwrap();
+        assert!(calibration.converged);
+        assert!((calibration.base_length-base_length).abs()<1e-6);
+        assert!((calibration.radius_left-radius_left).abs()<1e-7);
+        assert!((calibration.radius_right-radius_right).abs()<1e-7);
+        assert!((calibration.initial_heading-heading).abs()<1e-6);
+        assert!(calibration.residuals.max<1e-6);
+
+        assert!(super::calibrate_odometry(&angles,&truth[1..],0.1,0.021,0.3,50).is_none());
+    }
+
+    #[test]
+    fn skid_steer_calibration_test(){
+        // a tracked vehicle whose tracks slip outwards and whose ICR is ahead of the center
+        let (icr_left,icr_right,icr_x,heading) = (0.3f64,-0.35,0.05,-0.2);
+        let mut model = crate::skid_steer_motion_model::SkidSteerModel::new(icr_left,icr_right,icr_x);
+        let mut pose = crate::base::Model2D::new(1.0,0.5,heading);
+        let (mut l,mut r) = (0.0,0.0);
+        let mut distances = vec![(0.0,0.0)];
+        let mut truth = vec![(pose.x,pose.y)];
+        for step in 0..400{
+            l += 0.02 + 0.015*(step as f64*0.04).sin();
+            r += 0.02 - 0.015*(step as f64*0.04).sin();
+            pose = crate::base::MotionUpdate2D::update_coords_odometry_stateless(&mut model,pose,l,r);
+            distances.push((l,r));
+            truth.push((pose.x,pose.y));
+        }
+
+        // starts from a differential drive of the track spacing
+        let calibration = super::calibrate_skid_steer(&distances,&truth,0.5,0.0,50).unwrap();
+        assert!(calibration.converged);
+        assert!((calibration.icr_left-icr_left).abs()<1e-6);
+        assert!((calibration.icr_right-icr_right).abs()<1e-6);
+        assert!((calibration.icr_x-icr_x).abs()<1e-6);
+        assert!((calibration.initial_heading-heading).abs()<1e-6);
+        assert!(calibration.residuals.max<1e-6);
+        assert!((calibration.model().effective_base_length()-0.65).abs()<1e-6);
+    }
+
+    /// Runs a square with equal wheel readings on the sides and turns in place , on a robot whose
+    /// wheels and wheelbase differ from the nominal ones
+    fn square_run(clockwise:bool, side_length:f64, base_length:f64, diameter_ratio:f64, wheelbase_ratio:f64)->super::SquareRun<f64>{
+        let turn = if clockwise { -1.0 } else { 1.0 }*std::f64::consts::FRAC_PI_2*base_length/2.0;
+        let (scale_left,scale_right) = (2.0/(diameter_ratio + 1.0),2.0/(1.0/diameter_ratio + 1.0));
+        let mut actual = crate::odometry_motion_model::OdometryModel::new(base_length*wheelbase_ratio);
+        let mut pose = crate::base::Model2D::new(0.0,0.0,0.0);
+        let (mut l,mut r) = (0.0,0.0);
+        let mut readings = vec![(l,r)];
+        for _ in 0..4{
+            for (dl,dr) in [(side_length,side_length),(-turn,turn)].iter(){
+                for _ in 0..10{
+                    l += dl/10.0;
+                    r += dr/10.0;
+                    readings.push((l,r));
+                    pose = actual.update_coords_odometry_stateless(pose,l*scale_left,r*scale_right);
+                }
+            }
+        }
+        super::SquareRun::new(readings,(pose.x,pose.y))
+    }
+
+    #[test]
+    fn umbmark_test(){
+        let (side_length,base_length) = (4.0,0.3);
+        let cw = [square_run(true,side_length,base_length,1.001,1.01)];
+        let ccw = [square_run(false,side_length,base_length,1.001,1.01)];
+        let correction = super::umbmark(&cw,&ccw,side_length,base_length).unwrap();
+        assert!((correction.wheelbase_ratio-1.01).abs()<1e-3);
+        assert!((correction.diameter_ratio-1.001).abs()<1e-4);
+        assert!((correction.scale_right/correction.scale_left-correction.diameter_ratio).abs()<1e-9);
+
+        // dead reckoning the same runs with the correction brings the robot back much closer
+        let corrected = |runs:&[super::SquareRun<f64>]|->Vec<super::SquareRun<f64>>{
+            runs.iter().map(|run|{
+                let readings = run.readings.iter().map(|(l,r)| correction.wheel_distances(*l,*r)).collect();
+                super::SquareRun::new(readings,run.final_position)
+            }).collect()
+        };
+        let residual = super::umbmark(&corrected(&cw),&corrected(&ccw),side_length,correction.base_length).unwrap();
+        assert!(residual.max_systematic_error()<0.1*correction.max_systematic_error());
+
+        assert!(super::umbmark(&cw,&[],side_length,base_length).is_none());
+    }
+
+    #[test]
+    fn sample_data_calibration_test(){
+        let angles:Vec<(f32,f32)> = crate::io::webots_log::read_columns::<2,_>("sample_data/ws_pos8008.txt").unwrap().iter().map(|a| (a[0],a[1])).collect();
+        // the supervisor positions are in a left handed frame , mirror them into the odometry frame
+        let truth:Vec<(f32,f32)> = crate::io::webots_log::read_columns::<2,_>("sample_data/abs_pos8008.txt").unwrap().iter().map(|p| (p[0],-p[1])).collect();
+
+        let nominal = super::calibrate_odometry(&angles,&truth,0.1054,0.021,1.57,0).unwrap();
+        let calibration = super::calibrate_odometry(&angles,&truth,0.1054,0.021,1.57,50).unwrap();
+        assert!(calibration.converged);
+        // the simulated robot is close to its nominal geometry , with wheels of equal size
+        assert!((calibration.base_length-0.1054).abs()<1e-3);
+        assert!((calibration.radius_left-0.021).abs()<1e-4 && (calibration.radius_right-0.021).abs()<1e-4);
+        assert!((calibration.radius_left/calibration.radius_right-1.0).abs()<1e-3);
+        assert!((calibration.initial_heading-1.57).abs()<1e-2);
+        assert!(calibration.residuals.rmse<nominal.residuals.rmse);
+        assert!(calibration.residuals.rmse<0.05);
+        let deviations = calibration.standard_deviations();
+        assert!(deviations.iter().all(|d| d.is_finite() && *d<0.01));
+    }
+}
@@ -0,0 +1,333 @@
+//! Extended kalman filter localization (Probabilistic Robotics, Thrun et al. , table 7.2)
+//! The predict step is driven by any [base::MotionUpdate2D] implementor, the update step by any
+//! [MeasurementModel2D] implementor
+use crate::base;
+
+
+/// A measurement model with an M dimensional measurement of a [base::Model2D]
+pub trait MeasurementModel2D<const M:usize,T:base::Real=f32>{
+    /// Expected measurement at the given pose
+    fn predict_measurement(&self, pos:base::Model2D<T>)->[T;M];
+    /// Jacobian of the expected measurement with respect to (x, y, theta)
+    fn get_jacobian(&self, pos:base::Model2D<T>)->[[T;3];M];
+    /// Covariance of the measurement noise
+    fn get_noise(&self)->[[T;M];M];
+    /// Difference between the measurement and the expected measurement
+    /// Override this for measurements containing angles
+    fn residual(&self, measurement:&[T;M], expected:&[T;M])->[T;M]{
+        let mut out = [T::zero();M];
+        for (i,value) in out.iter_mut().enumerate(){
+            *value = measurement[i] - expected[i];
+        }
+        out
+    }
+}
+
+
+
+/// Direct measurement of the (x, y) position, e.g. a GPS or the webots supervisor
+pub struct PositionMeasurement<T:base::Real=f32>{
+    pub var_x:T,
+    pub var_y:T
+}
+impl<T:base::Real> PositionMeasurement<T>{
+    pub fn new(var_x:T,var_y:T)->PositionMeasurement<T>{
+        PositionMeasurement{
+            var_x,
+            var_y
+        }
+    }
+}
+
+impl<T:base::Real> MeasurementModel2D<2,T> for PositionMeasurement<T>{
+    fn predict_measurement(&self, pos:base::Model2D<T>)->[T;2]{
+        [pos.x,pos.y]
+    }
+
+    fn get_jacobian(&self, _pos:base::Model2D<T>)->[[T;3];2]{
+        let (zero,one) = (T::zero(),T::one());
+        [[one,zero,zero],[zero,one,zero]]
+    }
+
+    fn get_noise(&self)->[[T;2];2]{
+        [[self.var_x,T::zero()],[T::zero(),self.var_y]]
+    }
+}
+
+
+
+/// Range and bearing to a landmark at a known position
+pub struct RangeBearingMeasurement<T:base::Real=f32>{
+    pub landmark_x:T,
+    pub landmark_y:T,
+    pub var_range:T,
+    pub var_bearing:T
+}
+impl<T:base::Real> RangeBearingMeasurement<T>{
+    pub fn new(landmark_x:T,landmark_y:T,var_range:T,var_bearing:T)->RangeBearingMeasurement<T>{
+        RangeBearingMeasurement{
+            landmark_x,
+            landmark_y,
+            var_range,
+            var_bearing
+        }
+    }
+}
+
+impl<T:base::Real> MeasurementModel2D<2,T> for RangeBearingMeasurement<T>{
+    fn predict_measurement(&self, pos:base::Model2D<T>)->[T;2]{
+        let dx = self.landmark_x - pos.x;
+        let dy = self.landmark_y - pos.y;
+        [(dx*dx + dy*dy).sqrt(), base::normalize_angle(dy.atan2(dx) - pos.theta)]
+    }
+
+    fn get_jacobian(&self, pos:base::Model2D<T>)->[[T;3];2]{
+        let dx = self.landmark_x - pos.x;
+        let dy = self.landmark_y - pos.y;
+        let q = dx*dx + dy*dy;
+        let range = q.sqrt();
+        [
+            [-dx/range, -dy/range, T::zero()],
+            [dy/q, -dx/q, -T::one()]
+        ]
+    }
+
+    fn get_noise(&self)->[[T;2];2]{
+        [[self.var_range,T::zero()],[T::zero(),self.var_bearing]]
+    }
+
+    fn residual(&self, measurement:&[T;2], expected:&[T;2])->[T;2]{
+        [measurement[0] - expected[0], base::normalize_angle(measurement[1] - expected[1])]
+    }
+}
+
+
+
+/// Extended kalman filter over a [base::Model2D] pose
+pub struct ExtendedKalmanFilter<T:base::Real=f32>{
+    pub mean:base::Model2D<T>,
+    pub covariance:base::Covariance2D<T>
+}
+
+impl<T:base::Real> ExtendedKalmanFilter<T>{
+    pub fn new(mean:base::Model2D<T>, covariance:base::Covariance2D<T>)->ExtendedKalmanFilter<T>{
+        ExtendedKalmanFilter{
+            mean,
+            covariance
+        }
+    }
+
+
+    /// Prediction step, P = G*P*G^T + V*M*V^T
+    /// `control_noise` is the covariance M of the controls the control jacobian V is taken with
+    /// respect to (see [base::MotionUpdate2D::get_control_jacobian_stateless]). For the
+    /// differential drive models that is the wheel increments (dl, dr) in m^2 , for the
+    /// [crate::ackermann_motion_model::AckermannModel] the rear distance (m^2) and the steering
+    /// angle (rad^2) , for the [crate::bicycle_motion_model::BicycleModel] the velocity
+    /// ((m/s)^2) and the steering angle (rad^2).
+    /// The jacobians are taken before updating the coordinates, so models that store the odometry
+    /// readings (like [crate::odometry_motion_model::OdometryModel]) end up with the new readings.
+    pub fn predict<U:base::MotionUpdate2D<T>>(&mut self, model:&mut U, odom_l:T, odom_r:T, control_noise:&base::ControlCovariance2D<T>){
+        let g = model.get_jacobian_stateless(self.mean,odom_l,odom_r);
+        let v = model.get_control_jacobian_stateless(self.mean,odom_l,odom_r);
+        self.mean = model.update_coords_odometry_stateless(self.mean,odom_l,odom_r);
+        self.covariance = self.covariance.propagate(&g) + v.propagate(control_noise);
+    }
+
+
+    /// Correction step with the given measurement
+    /// Returns Err if the innovation covariance is singular, the filter is left unchanged
+    #[allow(clippy::result_unit_err)]
+    pub fn update<const M:usize,Z:MeasurementModel2D<M,T>>(&mut self, model:&Z, measurement:&[T;M])->Result<(),()>{
+        let p = self.covariance.to_matrix().data;
+        let h = model.get_jacobian(self.mean);
+        let noise = model.get_noise();
+        let expected = model.predict_measurement(self.mean);
+        let residual = model.residual(measurement,&expected);
+
+        // P*H^T (3xM)
+        let mut pht = [[T::zero();M];3];
+        for (i,row) in pht.iter_mut().enumerate(){
+            for (j,value) in row.iter_mut().enumerate(){
+                *value = (0..3).map(|k| p[i][k]*h[j][k]).sum();
+            }
+        }
+
+        // S = H*P*H^T + Q (MxM)
+        let mut s = noise;
+        for (i,row) in s.iter_mut().enumerate(){
+            for (j,value) in row.iter_mut().enumerate(){
+                *value += (0..3).map(|k| h[i][k]*pht[k][j]).sum::<T>();
+            }
+        }
+        let s_inv = invert(s).ok_or(())?;
+
+        // K = P*H^T*S^-1 (3xM)
+        let mut k = [[T::zero();M];3];
+        for (i,row) in k.iter_mut().enumerate(){
+            for (j,value) in row.iter_mut().enumerate(){
+                *value = (0..M).map(|l| pht[i][l]*s_inv[l][j]).sum();
+            }
+        }
+
+        let correction:Vec<T> = k.iter().map(|row| (0..M).map(|j| row[j]*residual[j]).sum()).collect();
+        self.mean = base::Model2D::new(self.mean.x + correction[0], self.mean.y + correction[1], self.mean.theta + correction[2]);
+
+        // P = (I - K*H)*P
+        let mut ikh = base::JacobianModel2D::identity();
+        for (i,row) in ikh.data.iter_mut().enumerate(){
+            for (j,value) in row.iter_mut().enumerate(){
+                *value -= (0..M).map(|l| k[i][l]*h[l][j]).sum::<T>();
+            }
+        }
+        self.covariance = base::Covariance2D::from_matrix(ikh*self.covariance);
+        Ok(())
+    }
+}
+
+
+
+/// Inverts a square matrix by gauss jordan elimination with partial pivoting
+/// Returns None if the matrix is singular
+pub(crate) fn invert<const M:usize,T:base::Real>(matrix:[[T;M];M])->Option<[[T;M];M]>{
+    let mut a = matrix;
+    let mut inv = [[T::zero();M];M];
+    for (i,row) in inv.iter_mut().enumerate(){
+        row[i] = T::one();
+    }
+
+    for col in 0..M{
+        let pivot = (col..M).max_by(|&i,&j| a[i][col].abs().partial_cmp(&a[j][col].abs()).unwrap_or(std::cmp::Ordering::Equal))?;
+        if a[pivot][col].abs() <= T::epsilon()*T::epsilon(){
+            return None
+        }
+        a.swap(col,pivot);
+        inv.swap(col,pivot);
+
+        let scale = a[col][col];
+        for j in 0..M{
+            a[col][j] /= scale;
+            inv[col][j] /= scale;
+        }
+        for row in 0..M{
+            if row==col{
+                continue
+            }
+            let factor = a[row][col];
+            for j in 0..M{
+                a[row][j] -= factor*a[col][j];
+                inv[row][j] -= factor*inv[col][j];
+            }
+        }
+    }
+    Some(inv)
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::MeasurementModel2D;
+
+    #[test]
+    fn invert_test(){
+        let m = [[4.0f32,7.0],[2.0,6.0]];
+        let inv = super::invert(m).unwrap();
+        assert!((inv[0][0]-0.6).abs()<1e-6 && (inv[0][1]+0.7).abs()<1e-6);
+        assert!((inv[1][0]+0.2).abs()<1e-6 && (inv[1][1]-0.4).abs()<1e-6);
+        assert!(super::invert([[1.0f32,2.0],[2.0,4.0]]).is_none());
+    }
+
+    #[test]
+    fn ekf_predict_update_test(){
+        let mut model = crate::odometry_motion_model::OdometryModel::new(0.1);
+        let mut ekf:super::ExtendedKalmanFilter = super::ExtendedKalmanFilter::new(
+            crate::base::Model2D::new(0.0,0.0,0.0),
+            crate::base::Covariance2D::diagonal(0.01,0.01,0.01));
+        let control_noise = crate::base::ControlCovariance2D::diagonal(1e-4,1e-4);
+
+        ekf.predict(&mut model,0.5,0.5,&control_noise);
+        assert!((ekf.mean.x-0.5).abs()<1e-6);
+        // uncertainty in theta becomes uncertainty in y
+        assert!(ekf.covariance.get(1,1)>0.01);
+        assert!(ekf.covariance.get(1,2)>0.0);
+
+        let var_y = ekf.covariance.get(1,1);
+        let sensor = super::PositionMeasurement::new(0.001,0.001);
+        ekf.update(&sensor,&[0.52,0.05]).unwrap();
+        assert!(ekf.covariance.get(1,1)<var_y);
+        assert!(ekf.mean.x>0.5 && ekf.mean.y>0.0);
+        // the correlation with y moves the heading as well
+        assert!(ekf.mean.theta>0.0);
+    }
+
+    #[test]
+    fn ekf_velocity_model_straight_line_test(){
+        // driving straight the heading uncertainty spreads sideways by the distance v*dt
+        let mut model = crate::velocity_motion_model::VelocityMotionModel::new(0.1f64,0.5);
+        let mut ekf = super::ExtendedKalmanFilter::new(
+            crate::base::Model2D::new(1.0,2.0,0.3),
+            crate::base::Covariance2D::diagonal(0.0,0.0,0.01));
+        let control_noise = crate::base::ControlCovariance2D::diagonal(0.0,0.0);
+        ekf.predict(&mut model,0.2,0.2,&control_noise);
+        let (sin_t,cos_t) = 0.3f64.sin_cos();
+        assert!((ekf.mean.x-(1.0+0.2*cos_t)).abs()<1e-12 && (ekf.mean.y-(2.0+0.2*sin_t)).abs()<1e-12);
+        assert!((ekf.covariance.get(0,2)+0.2*sin_t*0.01).abs()<1e-12);
+        assert!((ekf.covariance.get(1,2)-0.2*cos_t*0.01).abs()<1e-12);
+        assert!((ekf.covariance.get(1,1)-0.04*cos_t*cos_t*0.01).abs()<1e-12);
+    }
+
+    #[test]
+    fn ekf_ackermann_model_control_noise_test(){
+        // the control noise is (rear distance in m^2, steering angle in rad^2) , driving straight the
+        // distance noise stays along x and the steering noise turns the heading by ds/L per radian
+        let mut model = crate::ackermann_motion_model::AckermannModel::new(0.25f64);
+        let mut ekf = super::ExtendedKalmanFilter::new(
+            crate::base::Model2D::new(0.0,0.0,0.0),
+            crate::base::Covariance2D::diagonal(0.0,0.0,0.0));
+        let control_noise = crate::base::ControlCovariance2D::diagonal(1e-4,1e-2);
+        ekf.predict(&mut model,0.5,0.0,&control_noise);
+        assert!((ekf.mean.x-0.5).abs()<1e-12 && ekf.mean.y.abs()<1e-12);
+        let (ds,wheelbase) = (0.5,0.25);
+        assert!((ekf.covariance.get(0,0)-1e-4).abs()<1e-12);
+        assert!((ekf.covariance.get(2,2)-(ds/wheelbase)*(ds/wheelbase)*1e-2).abs()<1e-12);
+        assert!((ekf.covariance.get(1,1)-(ds*ds/(2.0*wheelbase)).powi(2)*1e-2).abs()<1e-12);
+        assert!((ekf.covariance.get(1,2)-ds*ds/(2.0*wheelbase)*(ds/wheelbase)*1e-2).abs()<1e-12);
+        assert!(ekf.covariance.get(0,2).abs()<1e-12);
+    }
+
+    #[test]
+    fn ekf_bicycle_model_control_noise_test(){
+        // the control noise is (velocity in (m/s)^2, steering angle in rad^2) , the velocity noise
+        // reaches x scaled by the time step
+        let mut model = crate::bicycle_motion_model::BicycleModel::new(0.25f64,0.1);
+        let mut ekf = super::ExtendedKalmanFilter::new(
+            crate::base::Model2D::new(0.0,0.0,0.0),
+            crate::base::Covariance2D::diagonal(0.0,0.0,0.0));
+        let control_noise = crate::base::ControlCovariance2D::diagonal(1e-2,1e-2);
+        ekf.predict(&mut model,2.0,0.0,&control_noise);
+        let (dt,wheelbase) = (0.1,0.25);
+        let ds = 2.0*dt;
+        assert!((ekf.mean.x-ds).abs()<1e-12 && ekf.mean.y.abs()<1e-12);
+        assert!((ekf.covariance.get(0,0)-dt*dt*1e-2).abs()<1e-12);
+        assert!((ekf.covariance.get(2,2)-(ds/wheelbase)*(ds/wheelbase)*1e-2).abs()<1e-12);
+        assert!((ekf.covariance.get(1,1)-(ds*ds/(2.0*wheelbase)).powi(2)*1e-2).abs()<1e-12);
+    }
+
+    #[test]
+    fn range_bearing_jacobian_test(){
+        let sensor:super::RangeBearingMeasurement = super::RangeBearingMeasurement::new(2.0,1.0,0.01,0.01);
+        let pos = crate::base::Model2D::new(0.5,-0.3,0.4);
+        let jacobian = sensor.get_jacobian(pos);
+        let h = 1e-3;
+        let offsets = [(h,0.0,0.0),(0.0,h,0.0),(0.0,0.0,h)];
+        for (col,o) in offsets.iter().enumerate(){
+            let plus = sensor.predict_measurement(crate::base::Model2D::new(pos.x+o.0,pos.y+o.1,pos.theta+o.2));
+            let minus = sensor.predict_measurement(crate::base::Model2D::new(pos.x-o.0,pos.y-o.1,pos.theta-o.2));
+            for row in 0..2{
+                assert!((jacobian[row][col]-(plus[row]-minus[row])/(2.0*h)).abs()<1e-2);
+            }
+        }
+    }
+}
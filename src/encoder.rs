@@ -0,0 +1,195 @@
+//! Wheel encoder front-end , turns raw tick counts into the wheel distances the motion models use
+//! Hardware counters are 16 or 32 bits wide and wrap around , consecutive readings are assumed to
+//! be less than half the counter range apart so the wraparound can be told from a reversal.
+use crate::base;
+use crate::odometry_motion_model::OdometryModel;
+
+
+/// Width of the hardware tick counter
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum CounterWidth{
+    Bits16,
+    Bits32
+}
+
+impl CounterWidth{
+    /// Number of distinct counter values , 2^bits
+    pub fn modulus(&self)->i64{
+        match self{
+            CounterWidth::Bits16=>1<<16,
+            CounterWidth::Bits32=>1<<32
+        }
+    }
+}
+
+
+
+/// A single wheel encoder. `inverted` flips the sign of the ticks , e.g. for the wheel whose
+/// encoder counts down when the robot moves forward because the motors are mounted mirrored
+#[derive(Clone,Debug)]
+pub struct WheelEncoder<T:base::Real=f32>{
+    pub ticks_per_revolution:u32,
+    pub wheel_radius:T,
+    pub width:CounterWidth,
+    pub inverted:bool,
+    last_ticks:Option<i64>,
+    total_ticks:i64
+}
+
+impl<T:base::Real> WheelEncoder<T>{
+    pub fn new(ticks_per_revolution:u32, wheel_radius:T, width:CounterWidth)->WheelEncoder<T>{
+        WheelEncoder{
+            ticks_per_revolution,
+            wheel_radius,
+            width,
+            inverted:false,
+            last_ticks:None,
+            total_ticks:0
+        }
+    }
+
+
+    /// Signed number of ticks between two raw counter values , taking the shorter way around the
+    /// counter
+    pub fn tick_difference(&self, previous:u32, current:u32)->i64{
+        let modulus = self.width.modulus();
+        let difference = (current as i64 - previous as i64).rem_euclid(modulus);
+        if difference >= modulus/2 { difference - modulus } else { difference }
+    }
+
+
+    /// Distance covered by the wheel for the given number of ticks
+    pub fn ticks_to_distance(&self, ticks:i64)->T{
+        let revolutions = ticks as f64/self.ticks_per_revolution as f64;
+        base::real::<T>(revolutions*std::f64::consts::TAU)*self.wheel_radius
+    }
+
+
+    /// Feeds a raw counter value , returns the distance covered since the previous value.
+    /// The first value only sets the reference and returns zero.
+    /// Values wider than the counter are truncated to its width
+    pub fn update(&mut self, raw_ticks:u32)->T{
+        let current = raw_ticks as i64 & (self.width.modulus() - 1);
+        let ticks = match self.last_ticks{
+            Some(previous)=>self.tick_difference(previous as u32,current as u32),
+            None=>0
+        };
+        let ticks = if self.inverted { -ticks } else { ticks };
+        self.last_ticks = Some(current);
+        self.total_ticks += ticks;
+        self.ticks_to_distance(ticks)
+    }
+
+
+    /// Distance covered since the first reading , computed from the unwrapped tick count so no
+    /// rounding error accumulates
+    pub fn distance(&self)->T{
+        self.ticks_to_distance(self.total_ticks)
+    }
+
+
+    /// Forgets the reference reading and the distance covered
+    pub fn reset(&mut self){
+        self.last_ticks = None;
+        self.total_ticks = 0;
+    }
+}
+
+
+
+/// The two encoders of a differential drive robot
+#[derive(Clone,Debug)]
+pub struct DifferentialEncoders<T:base::Real=f32>{
+    pub left:WheelEncoder<T>,
+    pub right:WheelEncoder<T>
+}
+
+impl<T:base::Real> DifferentialEncoders<T>{
+    pub fn new(left:WheelEncoder<T>, right:WheelEncoder<T>)->DifferentialEncoders<T>{
+        DifferentialEncoders{
+            left,
+            right
+        }
+    }
+
+
+    /// Feeds raw counter values of both wheels , returns the distance increments (dl, dr).
+    /// These are the readings of [crate::velocity_motion_model::VelocityMotionModel]
+    pub fn update(&mut self, left_ticks:u32, right_ticks:u32)->(T,T){
+        (self.left.update(left_ticks),self.right.update(right_ticks))
+    }
+
+
+    /// Distances covered by both wheels since the first reading.
+    /// These are the readings of [OdometryModel]
+    pub fn distances(&self)->(T,T){
+        (self.left.distance(),self.right.distance())
+    }
+
+
+    /// Feeds raw counter values and updates the pose of the odometry model
+    pub fn update_odometry_model(&mut self, model:&mut OdometryModel<T>, left_ticks:u32, right_ticks:u32)->base::Model2D<T>{
+        self.update(left_ticks,right_ticks);
+        let (odom_l,odom_r) = self.distances();
+        base::MotionUpdate2D::update_coords_odometry(model,odom_l,odom_r)
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::{CounterWidth,WheelEncoder};
+
+    #[test]
+    fn rollover_test(){
+        let encoder:WheelEncoder = WheelEncoder::new(100,0.5,CounterWidth::Bits16);
+        assert_eq!(encoder.tick_difference(65530,4),10);
+        assert_eq!(encoder.tick_difference(4,65530),-10);
+        assert_eq!(encoder.tick_difference(100,90),-10);
+        let encoder:WheelEncoder = WheelEncoder::new(100,0.5,CounterWidth::Bits32);
+        assert_eq!(encoder.tick_difference(u32::MAX-4,5),10);
+        assert_eq!(encoder.tick_difference(5,u32::MAX-4),-10);
+    }
+
+    #[test]
+    fn wheel_encoder_test(){
+        let mut encoder:WheelEncoder<f64> = WheelEncoder::new(1000,0.1,CounterWidth::Bits16);
+        assert_eq!(encoder.update(65000),0.0);
+        // 1036 ticks forward through the wraparound
+        let step = encoder.update(500);
+        assert!((step-1.036*std::f64::consts::TAU*0.1).abs()<1e-12);
+        // and back again
+        encoder.update(65000);
+        assert!(encoder.distance().abs()<1e-12);
+
+        encoder.reset();
+        encoder.inverted = true;
+        encoder.update(0);
+        encoder.update(65536-250);
+        assert!((encoder.distance()-0.25*std::f64::consts::TAU*0.1).abs()<1e-12);
+    }
+
+    #[test]
+    fn odometry_model_ticks_test(){
+        let wheel = WheelEncoder::new(360,0.021,CounterWidth::Bits16);
+        let mut left = wheel.clone();
+        left.inverted = true;
+        let mut encoders = super::DifferentialEncoders::new(left,wheel);
+        let mut model = crate::odometry_motion_model::OdometryModel::new(0.1054);
+        let mut reference = crate::odometry_motion_model::OdometryModel::new(0.1054);
+
+        // the left counter counts down from 100 and wraps , the right one counts up
+        let mut pose = encoders.update_odometry_model(&mut model,100,0);
+        for step in 1..=20u32{
+            let left_ticks = (100i64 - 30*step as i64).rem_euclid(65536) as u32;
+            pose = encoders.update_odometry_model(&mut model,left_ticks,25*step);
+        }
+        let circumference = std::f32::consts::TAU*0.021;
+        let expected = crate::base::MotionUpdate2D::update_coords_odometry(&mut reference,600.0/360.0*circumference,500.0/360.0*circumference);
+        assert!((pose.x-expected.x).abs()<1e-5);
+        assert!((pose.y-expected.y).abs()<1e-5);
+        assert!((pose.theta-expected.theta).abs()<1e-5);
+    }
+}
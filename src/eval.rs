@@ -0,0 +1,191 @@
+//! Comparison of estimated trajectories with ground truth
+//! Absolute trajectory error (ATE) and relative pose error (RPE) as defined by Sturm et al. ,
+//! "A benchmark for the evaluation of RGB-D SLAM systems". Both trajectories must have the same
+//! length and pose i of the estimate must correspond to pose i of the ground truth.
+use crate::base;
+
+
+/// Summary of a set of errors
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub struct ErrorStatistics<T:base::Real=f32>{
+    pub rmse:T,
+    pub mean:T,
+    pub median:T,
+    pub max:T,
+    pub count:usize
+}
+
+impl<T:base::Real> ErrorStatistics<T>{
+    /// Returns None if there are no errors
+    pub fn from_errors(errors:&[T])->Option<ErrorStatistics<T>>{
+        if errors.is_empty(){
+            return None
+        }
+        let count = errors.len();
+        let mut sorted = errors.to_vec();
+        sorted.sort_by(|a,b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let median = if count%2==1 { sorted[count/2] } else { (sorted[count/2 - 1] + sorted[count/2])/base::real(2.0) };
+        // accumulate in double precision , the logs have thousands of poses
+        let to_f64 = |e:&T| e.to_f64().unwrap_or(f64::NAN);
+        let sum:f64 = errors.iter().map(to_f64).sum();
+        let sum_squares:f64 = errors.iter().map(|e| to_f64(e)*to_f64(e)).sum();
+        Some(ErrorStatistics{
+            rmse:base::real((sum_squares/count as f64).sqrt()),
+            mean:base::real(sum/count as f64),
+            median,
+            max:sorted[count-1],
+            count
+        })
+    }
+}
+
+
+/// Rigid transformation (rotation then translation) that maps the estimate onto the ground truth
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub struct Alignment<T:base::Real=f32>{
+    pub rotation:T,
+    pub x:T,
+    pub y:T
+}
+
+impl<T:base::Real> Alignment<T>{
+    pub fn identity()->Alignment<T>{
+        Alignment{
+            rotation:T::zero(),
+            x:T::zero(),
+            y:T::zero()
+        }
+    }
+
+    pub fn apply(&self, pos:base::Model2D<T>)->base::Model2D<T>{
+        let (sin_r,cos_r) = self.rotation.sin_cos();
+        base::Model2D::new(
+            cos_r*pos.x - sin_r*pos.y + self.x,
+            sin_r*pos.x + cos_r*pos.y + self.y,
+            pos.theta + self.rotation)
+    }
+}
+
+
+/// Least squares SE(2) alignment of the positions of `estimate` onto `truth` (Umeyama without
+/// scale). Returns None if the trajectories are empty or of different length
+pub fn align_se2<T:base::Real>(estimate:&[base::Model2D<T>], truth:&[base::Model2D<T>])->Option<Alignment<T>>{
+    if estimate.is_empty() || estimate.len()!=truth.len(){
+        return None
+    }
+    let n = estimate.len() as f64;
+    let to_f64 = |value:T| value.to_f64().unwrap_or(f64::NAN);
+    let centroid = |poses:&[base::Model2D<T>]|{
+        let x:f64 = poses.iter().map(|p| to_f64(p.x)).sum();
+        let y:f64 = poses.iter().map(|p| to_f64(p.y)).sum();
+        (x/n,y/n)
+    };
+    let (ex,ey) = centroid(estimate);
+    let (tx,ty) = centroid(truth);
+
+    // the rotation maximizing sum(t_i . R e_i) over the centered points
+    let (mut dot,mut cross) = (0.0f64,0.0f64);
+    for (e,t) in estimate.iter().zip(truth.iter()){
+        let (ax,ay) = (to_f64(e.x) - ex, to_f64(e.y) - ey);
+        let (bx,by) = (to_f64(t.x) - tx, to_f64(t.y) - ty);
+        dot += ax*bx + ay*by;
+        cross += ax*by - ay*bx;
+    }
+    let rotation = cross.atan2(dot);
+    let (sin_r,cos_r) = rotation.sin_cos();
+    Some(Alignment{
+        rotation:base::real(rotation),
+        x:base::real(tx - (cos_r*ex - sin_r*ey)),
+        y:base::real(ty - (sin_r*ex + cos_r*ey))
+    })
+}
+
+
+/// Absolute trajectory error , the distances between corresponding positions.
+/// If `align` is set the estimate is first aligned with [align_se2].
+/// Returns None if the trajectories are empty or of different length
+pub fn absolute_trajectory_error<T:base::Real>(estimate:&[base::Model2D<T>], truth:&[base::Model2D<T>], align:bool)->Option<ErrorStatistics<T>>{
+    if estimate.len()!=truth.len(){
+        return None
+    }
+    let alignment = if align { align_se2(estimate,truth)? } else { Alignment::identity() };
+    let errors:Vec<T> = estimate.iter().zip(truth.iter()).map(|(e,t)|{
+        let e = alignment.apply(*e);
+        ((e.x - t.x).powi(2) + (e.y - t.y).powi(2)).sqrt()
+    }).collect();
+    ErrorStatistics::from_errors(&errors)
+}
+
+
+/// Translational and rotational parts of the relative pose error
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub struct RelativePoseError<T:base::Real=f32>{
+    pub translation:ErrorStatistics<T>,
+    pub rotation:ErrorStatistics<T>
+}
+
+
+/// Relative pose error over a fixed number of steps `delta` , the drift of the estimate between
+/// pose i and pose i+delta compared to the ground truth. The rotational error is in radians.
+/// Returns None if the trajectories are of different length or shorter than delta+1
+pub fn relative_pose_error<T:base::Real>(estimate:&[base::Model2D<T>], truth:&[base::Model2D<T>], delta:usize)->Option<RelativePoseError<T>>{
+    if estimate.len()!=truth.len() || delta==0 || estimate.len()<=delta{
+        return None
+    }
+    let (translation,rotation):(Vec<T>,Vec<T>) = (0..estimate.len()-delta).map(|i|{
+        let truth_step = truth[i+delta].relative_to(truth[i]);
+        let estimate_step = estimate[i+delta].relative_to(estimate[i]);
+        let error = estimate_step.relative_to(truth_step);
+        ((error.x*error.x + error.y*error.y).sqrt(), error.theta.abs())
+    }).unzip();
+    Some(RelativePoseError{
+        translation:ErrorStatistics::from_errors(&translation)?,
+        rotation:ErrorStatistics::from_errors(&rotation)?
+    })
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::base::Model2D;
+
+    #[test]
+    fn statistics_test(){
+        let stats = super::ErrorStatistics::from_errors(&[1.0f32,3.0,2.0,4.0]).unwrap();
+        assert_eq!(stats.median,2.5);
+        assert_eq!(stats.mean,2.5);
+        assert_eq!(stats.max,4.0);
+        assert!((stats.rmse-7.5f32.sqrt()).abs()<1e-6);
+        assert!(super::ErrorStatistics::<f32>::from_errors(&[]).is_none());
+    }
+
+    #[test]
+    fn aligned_ate_test(){
+        let truth:Vec<Model2D> = (0..20).map(|i| Model2D::new(0.1*i as f32,(0.3*i as f32).sin(),0.2)).collect();
+        // the same trajectory rotated and shifted
+        let transform = super::Alignment{rotation:0.7,x:-1.0,y:2.0};
+        let estimate:Vec<Model2D> = truth.iter().map(|p| transform.apply(*p)).collect();
+
+        let unaligned = super::absolute_trajectory_error(&estimate,&truth,false).unwrap();
+        assert!(unaligned.rmse>1.0);
+        let aligned = super::absolute_trajectory_error(&estimate,&truth,true).unwrap();
+        assert!(aligned.max<1e-4);
+
+        // a rigid transformation leaves the relative poses unchanged
+        let rpe = super::relative_pose_error(&estimate,&truth,3).unwrap();
+        assert!(rpe.translation.max<1e-4 && rpe.rotation.max<1e-4);
+        assert!(super::relative_pose_error(&estimate,&truth[1..],3).is_none());
+    }
+
+    #[test]
+    fn rpe_drift_test(){
+        let truth:Vec<Model2D> = (0..10).map(|i| Model2D::new(i as f32,0.0,0.0)).collect();
+        // the estimate travels 10% too far
+        let estimate:Vec<Model2D> = (0..10).map(|i| Model2D::new(1.1*i as f32,0.0,0.0)).collect();
+        let rpe = super::relative_pose_error(&estimate,&truth,2).unwrap();
+        assert!((rpe.translation.mean-0.2).abs()<1e-5);
+        assert_eq!(rpe.rotation.max,0.0);
+    }
+}
@@ -0,0 +1,175 @@
+//! Inverse kinematics of a differential drive , from the body twist (v, omega) or a target arc to
+//! the speeds of the left and right wheels.
+//! The wheel speeds are linear speeds (wheel radius * angular speed) , the same units as the
+//! DISTANCES the [OdometryModel] and [VelocityMotionModel] take.
+use crate::base;
+use crate::odometry_motion_model::OdometryModel;
+use crate::velocity_motion_model::VelocityMotionModel;
+
+
+/// Computes wheel commands for a differential drive of the given base length , optionally
+/// saturating them. `max_wheel_speed` bounds the speed of each wheel and `max_wheel_acceleration`
+/// its change from the last command. Both wheel speeds are scaled by the same factor , so a
+/// saturated command drives slower on the same curvature.
+#[derive(Clone,Debug)]
+pub struct DifferentialDriveKinematics<T:base::Real=f32>{
+    base_length:T,
+    pub max_wheel_speed:Option<T>,
+    pub max_wheel_acceleration:Option<T>,
+    last_command:(T,T)
+}
+
+impl<T:base::Real> DifferentialDriveKinematics<T>{
+
+    /// No saturation , the robot is at rest
+    pub fn new(base_length:T)->DifferentialDriveKinematics<T>{
+        DifferentialDriveKinematics{
+            base_length,
+            max_wheel_speed:None,
+            max_wheel_acceleration:None,
+            last_command:(T::zero(),T::zero())
+        }
+    }
+
+    pub fn from_odometry_model(model:&OdometryModel<T>)->DifferentialDriveKinematics<T>{
+        Self::new(model.base_length())
+    }
+
+    pub fn from_velocity_model(model:&VelocityMotionModel<T>)->DifferentialDriveKinematics<T>{
+        Self::new(model.base_length())
+    }
+
+
+    /// Wheel speeds (left, right) for the forward velocity v and the rotational velocity omega
+    pub fn wheel_speeds(&self, v:T, omega:T)->(T,T){
+        let turn = omega*self.base_length/base::real::<T>(2.0);
+        (v - turn, v + turn)
+    }
+
+
+    /// Forward kinematics , the body twist (v, omega) of the wheel speeds (left, right)
+    pub fn body_twist(&self, speed_l:T, speed_r:T)->(T,T){
+        ((speed_l + speed_r)/base::real::<T>(2.0), (speed_r - speed_l)/self.base_length)
+    }
+
+
+    /// Wheel speeds that drive the arc of length `distance` turning by `angle` in `duration`.
+    /// A zero angle drives straight and a zero distance turns in place
+    pub fn arc_wheel_speeds(&self, distance:T, angle:T, duration:T)->(T,T){
+        self.wheel_speeds(distance/duration,angle/duration)
+    }
+
+
+    /// The last command returned by [DifferentialDriveKinematics::command]
+    pub fn last_command(&self)->(T,T){
+        self.last_command
+    }
+
+    /// Sets the last command , e.g. to the measured wheel speeds
+    pub fn set_last_command(&mut self, speed_l:T, speed_r:T){
+        self.last_command = (speed_l,speed_r);
+    }
+
+
+    /// Saturates the wheel speeds (left, right) for a command held `time_step` after the last one.
+    /// The speeds are scaled by the largest factor in [0, 1] that satisfies the limits. If no
+    /// factor reaches the limits of the acceleration (the curvature changes too much in one step)
+    /// the change of each wheel is clamped instead and the curvature is only approached.
+    /// This does not change the state
+    pub fn saturate(&self, speed_l:T, speed_r:T, time_step:T)->(T,T){
+        let mut scale_max = T::one();
+        if let Some(max_speed) = self.max_wheel_speed{
+            let peak = speed_l.abs().max(speed_r.abs());
+            if peak > max_speed{
+                scale_max = max_speed/peak;
+            }
+        }
+        let max_change = match self.max_wheel_acceleration{
+            Some(max_acceleration)=>max_acceleration*time_step,
+            None=>return (speed_l*scale_max,speed_r*scale_max)
+        };
+
+        // each wheel allows the scales k with |k*speed - last| <= max_change
+        let (mut lower,mut upper) = (T::zero(),scale_max);
+        let mut feasible = true;
+        for (speed,last) in [(speed_l,self.last_command.0),(speed_r,self.last_command.1)].iter(){
+            if *speed==T::zero(){
+                feasible = feasible && last.abs() <= max_change;
+                continue
+            }
+            let (a,b) = ((*last - max_change)/ *speed, (*last + max_change)/ *speed);
+            lower = lower.max(a.min(b));
+            upper = upper.min(a.max(b));
+        }
+        if feasible && lower <= upper{
+            return (speed_l*upper,speed_r*upper)
+        }
+        let clamp = |speed:T,last:T| last + (speed*scale_max - last).max(-max_change).min(max_change);
+        (clamp(speed_l,self.last_command.0),clamp(speed_r,self.last_command.1))
+    }
+
+
+    /// Saturated wheel speeds for (v, omega) held `time_step` , stored as the last command
+    pub fn command(&mut self, v:T, omega:T, time_step:T)->(T,T){
+        let (speed_l,speed_r) = self.wheel_speeds(v,omega);
+        self.last_command = self.saturate(speed_l,speed_r,time_step);
+        self.last_command
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn differential_inverse_kinematics_test(){
+        let kinematics = super::DifferentialDriveKinematics::new(0.5f64);
+        let (l,r) = kinematics.wheel_speeds(1.0,2.0);
+        assert!((l-0.5).abs()<1e-12 && (r-1.5).abs()<1e-12);
+        let (v,omega) = kinematics.body_twist(l,r);
+        assert!((v-1.0).abs()<1e-12 && (omega-2.0).abs()<1e-12);
+
+        // a quarter circle of radius 2 in 4 seconds , then a half turn in place
+        let quarter = std::f64::consts::FRAC_PI_2;
+        let (l,r) = kinematics.arc_wheel_speeds(2.0*quarter,quarter,4.0);
+        assert!((l-1.75*quarter/4.0).abs()<1e-12 && (r-2.25*quarter/4.0).abs()<1e-12);
+        let (l,r) = kinematics.arc_wheel_speeds(0.0,2.0*quarter,1.0);
+        assert!((l+r).abs()<1e-12 && (r-0.25*2.0*quarter).abs()<1e-12);
+
+        // the velocity motion model driven by the wheel speeds has the same twist
+        let mut model = crate::velocity_motion_model::VelocityMotionModel::new(0.5f64,0.1);
+        let kinematics = super::DifferentialDriveKinematics::from_velocity_model(&model);
+        let (l,r) = kinematics.wheel_speeds(0.8,-1.2);
+        let change = model.update_get_radius_angle_distance(l*0.1,r*0.1).change();
+        assert!((change.s-0.8).abs()<1e-12 && (change.alpha+1.2).abs()<1e-12);
+    }
+
+    #[test]
+    fn wheel_saturation_test(){
+        let mut kinematics = super::DifferentialDriveKinematics::new(0.5f64);
+        kinematics.max_wheel_speed = Some(1.0);
+        let curvature = |(l,r):(f64,f64)| 2.0*(r-l)/(0.5*(l+r));
+        let command = kinematics.command(2.0,2.0,0.1);
+        assert!((command.0-0.6).abs()<1e-12 && (command.1-1.0).abs()<1e-12);
+        assert!((curvature(command)-curvature(kinematics.wheel_speeds(2.0,2.0))).abs()<1e-12);
+
+        // from rest the robot speeds up along the same curvature
+        kinematics.max_wheel_acceleration = Some(2.0);
+        kinematics.set_last_command(0.0,0.0);
+        let target = kinematics.wheel_speeds(0.5,1.0);
+        let mut command = (0.0,0.0);
+        for step in 0..10{
+            let last = kinematics.last_command();
+            command = kinematics.command(0.5,1.0,0.1);
+            assert!((command.0-last.0).abs()<=0.2+1e-12 && (command.1-last.1).abs()<=0.2+1e-12);
+            assert!((curvature(command)-curvature(target)).abs()<1e-9,"{}",step);
+        }
+        assert!((command.0-target.0).abs()<1e-12 && (command.1-target.1).abs()<1e-12);
+
+        // reversing the turn can not keep the curvature , each wheel changes as much as it may
+        kinematics.set_last_command(1.0,-1.0);
+        let command = kinematics.command(1.0,0.0,0.1);
+        assert!((command.0-1.0).abs()<1e-12 && (command.1+0.8).abs()<1e-12);
+    }
+}
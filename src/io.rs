@@ -0,0 +1,2 @@
+//! Readers for the log files recorded in simulation
+pub mod webots_log;
@@ -0,0 +1,213 @@
+//! Parser for the line based logs written by the webots controllers (see `sample_data`)
+//!
+//! `WS:left|right` wheel angles in radians (position sensors)
+//! `US:a|b|c` ultrasonic ranges
+//! `inf|inf|3.49|inf|inf|inf|inf|inf|` the 8 infra red ranges , `inf` when nothing is in range
+//!
+//! Empty lines are skipped. The ground truth files (`ws_pos8008.txt`, `abs_pos8008.txt`) have no
+//! prefix and are read with [read_columns].
+//!
+//! Values are parsed as `f32` , the precision the controllers write them with. Convert with
+//! `base::real` to run the models in `f64`.
+use std::io::BufRead;
+use std::path::Path;
+
+
+/// Number of infra red beams in a log line
+pub const IR_BEAMS:usize = 8;
+
+/// Number of ultrasonic sensors in a log line
+pub const US_SENSORS:usize = 3;
+
+
+/// A single line of a webots log
+#[derive(Clone,Debug,PartialEq)]
+pub enum WebotsRecord{
+    /// Angles of the left and right wheel, multiply by the wheel radius to get distances
+    WheelAngles{left:f32,right:f32},
+    Ultrasonic([f32;US_SENSORS]),
+    InfraRed([f32;IR_BEAMS])
+}
+
+
+#[derive(Debug)]
+pub enum ParseErrorKind{
+    Io(std::io::Error),
+    UnknownPrefix(String),
+    InvalidNumber(String),
+    WrongFieldCount{expected:usize,found:usize}
+}
+
+
+/// An error while reading a log , `line` starts at 1
+#[derive(Debug)]
+pub struct ParseError{
+    pub line:usize,
+    pub kind:ParseErrorKind
+}
+
+impl std::fmt::Display for ParseError{
+    fn fmt(&self, f:&mut std::fmt::Formatter<'_>)->std::fmt::Result{
+        match &self.kind{
+            ParseErrorKind::Io(e)=>write!(f,"line {}: {}",self.line,e),
+            ParseErrorKind::UnknownPrefix(p)=>write!(f,"line {}: unknown prefix '{}'",self.line,p),
+            ParseErrorKind::InvalidNumber(n)=>write!(f,"line {}: invalid number '{}'",self.line,n),
+            ParseErrorKind::WrongFieldCount{expected,found}=>write!(f,"line {}: expected {} fields, found {}",self.line,expected,found)
+        }
+    }
+}
+
+impl std::error::Error for ParseError{
+    fn source(&self)->Option<&(dyn std::error::Error + 'static)>{
+        match &self.kind{
+            ParseErrorKind::Io(e)=>Some(e),
+            _=>None
+        }
+    }
+}
+
+
+
+/// Parses `|` separated numbers , a trailing `|` is allowed
+fn parse_fields<const N:usize>(text:&str)->Result<[f32;N],ParseErrorKind>{
+    let text = text.trim();
+    let text = text.strip_suffix('|').unwrap_or(text);
+    let fields:Vec<&str> = text.split('|').collect();
+    if fields.len()!=N{
+        return Err(ParseErrorKind::WrongFieldCount{expected:N,found:fields.len()})
+    }
+    let mut out = [0.0;N];
+    for (value,field) in out.iter_mut().zip(fields.iter()){
+        *value = field.trim().parse::<f32>().map_err(|_| ParseErrorKind::InvalidNumber(field.to_string()))?;
+    }
+    Ok(out)
+}
+
+
+/// Parses a single line , returns None for empty lines
+pub fn parse_line(line:&str)->Result<Option<WebotsRecord>,ParseErrorKind>{
+    let line = line.trim();
+    if line.is_empty(){
+        return Ok(None)
+    }
+    match line.split_once(':'){
+        Some(("WS",values))=>{
+            let [left,right] = parse_fields::<2>(values)?;
+            Ok(Some(WebotsRecord::WheelAngles{left,right}))
+        },
+        Some(("US",values))=>Ok(Some(WebotsRecord::Ultrasonic(parse_fields(values)?))),
+        Some((prefix,_))=>Err(ParseErrorKind::UnknownPrefix(prefix.to_string())),
+        None=>Ok(Some(WebotsRecord::InfraRed(parse_fields(line)?)))
+    }
+}
+
+
+/// Iterator over the records of a log
+pub struct WebotsLogReader<R:BufRead>{
+    lines:std::io::Lines<R>,
+    line:usize
+}
+
+impl<R:BufRead> WebotsLogReader<R>{
+    pub fn new(reader:R)->WebotsLogReader<R>{
+        WebotsLogReader{
+            lines:reader.lines(),
+            line:0
+        }
+    }
+}
+
+impl<R:BufRead> Iterator for WebotsLogReader<R>{
+    type Item = Result<WebotsRecord,ParseError>;
+
+    fn next(&mut self)->Option<Self::Item>{
+        loop{
+            let text = self.lines.next()?;
+            self.line += 1;
+            let line = self.line;
+            let parsed = text.map_err(ParseErrorKind::Io).and_then(|t| parse_line(&t));
+            match parsed{
+                Ok(Some(record))=>return Some(Ok(record)),
+                Ok(None)=>continue,
+                Err(kind)=>return Some(Err(ParseError{line,kind}))
+            }
+        }
+    }
+}
+
+
+/// Reads every record of the log at `path` , stops at the first error
+pub fn read_log<P:AsRef<Path>>(path:P)->Result<Vec<WebotsRecord>,ParseError>{
+    let file = std::fs::File::open(path).map_err(|e| ParseError{line:0,kind:ParseErrorKind::Io(e)})?;
+    WebotsLogReader::new(std::io::BufReader::new(file)).collect()
+}
+
+
+/// The wheel angles (left, right) of the log in order
+pub fn wheel_angles(records:&[WebotsRecord])->Vec<(f32,f32)>{
+    records.iter().filter_map(|r| match r{
+        WebotsRecord::WheelAngles{left,right}=>Some((*left,*right)),
+        _=>None
+    }).collect()
+}
+
+
+/// Reads a file of `|` separated numbers with N columns per line, empty lines are skipped
+pub fn read_columns<const N:usize,P:AsRef<Path>>(path:P)->Result<Vec<[f32;N]>,ParseError>{
+    let file = std::fs::File::open(path).map_err(|e| ParseError{line:0,kind:ParseErrorKind::Io(e)})?;
+    let mut out = Vec::new();
+    for (index,text) in std::io::BufReader::new(file).lines().enumerate(){
+        let line = index + 1;
+        let text = text.map_err(|e| ParseError{line,kind:ParseErrorKind::Io(e)})?;
+        if text.trim().is_empty(){
+            continue
+        }
+        out.push(parse_fields(&text).map_err(|kind| ParseError{line,kind})?);
+    }
+    Ok(out)
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::{WebotsRecord,ParseErrorKind};
+
+    #[test]
+    fn parse_line_test(){
+        assert_eq!(super::parse_line("WS:0.1326|0.1326").unwrap(),Some(WebotsRecord::WheelAngles{left:0.1326,right:0.1326}));
+        assert_eq!(super::parse_line("US:1.97856|2.01543|1.97918").unwrap(),Some(WebotsRecord::Ultrasonic([1.97856,2.01543,1.97918])));
+        match super::parse_line("inf|inf|3.49679|inf|inf|inf|inf|inf|").unwrap(){
+            Some(WebotsRecord::InfraRed(ranges))=>{
+                assert_eq!(ranges[2],3.49679);
+                assert!(ranges[0].is_infinite());
+            },
+            other=>panic!("{:?}",other)
+        }
+        assert_eq!(super::parse_line("  ").unwrap(),None);
+        assert!(matches!(super::parse_line("XX:1|2"),Err(ParseErrorKind::UnknownPrefix(_))));
+        assert!(matches!(super::parse_line("WS:1|a"),Err(ParseErrorKind::InvalidNumber(_))));
+        assert!(matches!(super::parse_line("WS:1|2|3"),Err(ParseErrorKind::WrongFieldCount{expected:2,found:3})));
+    }
+
+    #[test]
+    fn reader_line_numbers_test(){
+        let text = "\nWS:1|2\nUS:1|2|3\n\nWS:1|x\n";
+        let records:Vec<_> = super::WebotsLogReader::new(text.as_bytes()).collect();
+        assert_eq!(records.len(),3);
+        let err = records[2].as_ref().unwrap_err();
+        assert_eq!(err.line,5);
+        assert_eq!(err.to_string(),"line 5: invalid number 'x'");
+    }
+
+    #[test]
+    fn sample_data_test(){
+        let records = super::read_log("sample_data/test_us8008.txt").unwrap();
+        assert_eq!(super::wheel_angles(&records).len(),4306);
+        let records = super::read_log("sample_data/test8008.txt").unwrap();
+        assert_eq!(records.iter().filter(|r| matches!(r,WebotsRecord::InfraRed(_))).count(),1954);
+        let positions = super::read_columns::<2,_>("sample_data/abs_pos8008.txt").unwrap();
+        assert_eq!(positions.len(),4955);
+    }
+}
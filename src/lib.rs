@@ -1,23 +1,48 @@
 pub mod odometry_motion_model;
 pub mod velocity_motion_model;
+pub mod ackermann_motion_model;
+pub mod bicycle_motion_model;
+pub mod mecanum_motion_model;
+pub mod skid_steer_motion_model;
+pub mod swerve_motion_model;
+pub mod probability;
+pub mod ekf;
+pub mod ukf;
+pub mod particle_filter;
+pub mod io;
+pub mod eval;
+pub mod encoder;
+pub mod calibration;
+pub mod inverse_kinematics;
 
 
 #[cfg(test)]
 mod tests;
 pub mod base{
 
-    type Real=f32;
+    /// Scalar type of the models , implemented for f32 (on the robot) and f64 (on the desktop ,
+    /// long runs accumulate noticeable drift in single precision)
+    pub trait Real:num_traits::Float + num_traits::FloatConst + std::iter::Sum + std::fmt::Debug + std::fmt::Display
+        + std::ops::AddAssign + std::ops::SubAssign + std::ops::MulAssign + std::ops::DivAssign + Default + 'static{}
+
+    impl<T> Real for T where T:num_traits::Float + num_traits::FloatConst + std::iter::Sum + std::fmt::Debug + std::fmt::Display
+        + std::ops::AddAssign + std::ops::SubAssign + std::ops::MulAssign + std::ops::DivAssign + Default + 'static{}
+
+    /// Converts a constant to the scalar type
+    pub fn real<T:Real>(value:f64)->T{
+        T::from(value).unwrap()
+    }
    
-    #[derive(Copy,Clone)]
-    pub struct Model2D{
-        pub x:f32,
-        pub y:f32,
-        pub theta:f32
+    #[derive(Copy,Clone,Debug,PartialEq)]
+    pub struct Model2D<T:Real=f32>{
+        pub x:T,
+        pub y:T,
+        pub theta:T
     }
     
-    impl Model2D{
+    impl<T:Real> Model2D<T>{
         
-        pub fn new(x:f32,y:f32,theta:f32)->Model2D{
+        pub fn new(x:T,y:T,theta:T)->Model2D<T>{
             Model2D{
                 x,
                 y,
@@ -26,43 +51,629 @@ pub mod base{
         }
     }
 
+    /// Wraps an angle to the interval [-pi, pi)
+    pub fn normalize_angle<T:Real>(angle:T)->T{
+        let two_pi = T::TAU();
+        let wrapped = angle - two_pi*((angle + T::PI())/two_pi).floor();
+        if wrapped >= T::PI() { wrapped - two_pi } else { wrapped }
+    }
+
+
+
+    /// How the motion models keep the heading of the pose
+    #[derive(Copy,Clone,Debug,Default,PartialEq,Eq)]
+    pub enum HeadingPolicy{
+        /// Wrapped to [-pi, pi)
+        WrapSigned,
+        /// Wrapped to [0, 2pi)
+        WrapPositive,
+        /// The sum of all rotations , unbounded
+        #[default]
+        Continuous
+    }
+
+    impl HeadingPolicy{
+        pub fn apply<T:Real>(&self, heading:T)->T{
+            match self{
+                HeadingPolicy::WrapSigned=>normalize_angle(heading),
+                HeadingPolicy::WrapPositive=>{
+                    let wrapped = heading - T::TAU()*(heading/T::TAU()).floor();
+                    if wrapped >= T::TAU() { wrapped - T::TAU() } else { wrapped }
+                }
+                HeadingPolicy::Continuous=>heading
+            }
+        }
+    }
+
+
+    /// Applies a [HeadingPolicy] to the pose of a model and counts the full turns it wraps away ,
+    /// so the unwrapped heading stays available
+    #[derive(Copy,Clone,Debug,Default,PartialEq,Eq)]
+    pub struct HeadingTracker{
+        pub policy:HeadingPolicy,
+        wraps:i64
+    }
+
+    impl HeadingTracker{
+        pub fn new(policy:HeadingPolicy)->HeadingTracker{
+            HeadingTracker{
+                policy,
+                wraps:0
+            }
+        }
+
+        /// Wraps the new heading of the model and counts the wrapped turns
+        pub fn update<T:Real>(&mut self, heading:T)->T{
+            let wrapped = self.policy.apply(heading);
+            self.wraps += ((heading - wrapped)/T::TAU()).round().to_i64().unwrap_or(0);
+            wrapped
+        }
+
+        /// The heading of the model before wrapping , the sum of all rotations
+        pub fn unwrapped<T:Real>(&self, heading:T)->T{
+            heading + T::TAU()*real(self.wraps as f64)
+        }
+
+        /// Number of complete turns of the unwrapped heading , positive counter clockwise
+        pub fn turns<T:Real>(&self, heading:T)->i64{
+            (self.unwrapped(heading)/T::TAU()).trunc().to_i64().unwrap_or(0)
+        }
+    }
+
+    /// Coefficients (sin(phi)/phi , (1 - cos(phi))/phi) of a motion on an arc turning by phi.
+    /// (1 - cos) is written with the half angle so they stay accurate for tiny rotations
+    pub fn arc_coefficients<T:Real>(phi:T)->(T,T){
+        if phi==T::zero(){
+            return (T::one(),T::zero())
+        }
+        let half_sin = (phi/real(2.0)).sin();
+        (phi.sin()/phi, real::<T>(2.0)*half_sin*half_sin/phi)
+    }
+
+
+    /// Derivatives of [arc_coefficients] with respect to phi. The closed forms cancel for small
+    /// phi , there the taylor series are used
+    pub fn arc_coefficient_derivatives<T:Real>(phi:T)->(T,T){
+        if phi.abs() < real(0.5){
+            let p2 = phi*phi;
+            let a = phi*(-T::one()/real(3.0) + p2*(T::one()/real(30.0) + p2*(-T::one()/real(840.0) + p2/real(45360.0))));
+            let b = real::<T>(0.5) + p2*(-T::one()/real(8.0) + p2*(T::one()/real(144.0) - p2/real(5760.0)));
+            return (a,b)
+        }
+        let (sin_p,cos_p) = phi.sin_cos();
+        ((phi*cos_p - sin_p)/(phi*phi), (phi*sin_p - T::one() + cos_p)/(phi*phi))
+    }
+
+
+    /// Moves `state` by a displacement given in the robot frame (forward, left, rotation), assuming
+    /// the body velocity was constant over the motion , the robot moves on a circle when it
+    /// rotates. This is the exponential map of SE(2)
+    pub fn integrate_body_motion<T:Real>(state:Model2D<T>, forward:T, left:T, rotation:T)->Model2D<T>{
+        let (a,b) = arc_coefficients(rotation);
+        let (dx,dy) = (a*forward - b*left, b*forward + a*left);
+        let (sin_t,cos_t) = state.theta.sin_cos();
+        Model2D::new(
+            state.x + cos_t*dx - sin_t*dy,
+            state.y + sin_t*dx + cos_t*dy,
+            state.theta + rotation)
+    }
+
+
+    /// Poses as elements of the lie group SE(2). A pose maps points from its own frame to the
+    /// frame it is given in , composition chains frames and the tangent vectors (vx, vy, omega)
+    /// are body velocities held for a unit of time.
+    /// Composition keeps the sum of the headings like the motion models , [Model2D::relative_to]
+    /// and [Model2D::log] wrap the heading to [-pi, pi)
+    impl<T:Real> Model2D<T>{
+        pub fn identity()->Model2D<T>{
+            Model2D::new(T::zero(),T::zero(),T::zero())
+        }
+
+        /// The same pose with the heading wrapped to [-pi, pi)
+        pub fn normalized(&self)->Model2D<T>{
+            Model2D::new(self.x,self.y,normalize_angle(self.theta))
+        }
+
+        /// self ⊕ other , the pose `other` given in the frame of self
+        pub fn compose(&self, other:Model2D<T>)->Model2D<T>{
+            let (sin_t,cos_t) = self.theta.sin_cos();
+            Model2D::new(
+                self.x + cos_t*other.x - sin_t*other.y,
+                self.y + sin_t*other.x + cos_t*other.y,
+                self.theta + other.theta)
+        }
+
+        /// The pose of the frame self is given in , seen from self
+        pub fn inverse(&self)->Model2D<T>{
+            let (sin_t,cos_t) = self.theta.sin_cos();
+            Model2D::new(
+                -cos_t*self.x - sin_t*self.y,
+                sin_t*self.x - cos_t*self.y,
+                -self.theta)
+        }
+
+        /// self ⊖ reference , the pose self in the frame of `reference` so that
+        /// reference ⊕ (self ⊖ reference) = self
+        pub fn relative_to(&self, reference:Model2D<T>)->Model2D<T>{
+            reference.inverse().compose(*self).normalized()
+        }
+
+        /// Exponential map , the pose reached from the identity with the body velocity
+        /// (vx, vy, omega) held for a unit of time
+        pub fn exp(vx:T, vy:T, omega:T)->Model2D<T>{
+            integrate_body_motion(Model2D::identity(),vx,vy,omega)
+        }
+
+        /// Logarithm map , the body velocity (vx, vy, omega) whose exponential is this pose ,
+        /// omega in [-pi, pi)
+        pub fn log(&self)->(T,T,T){
+            let omega = normalize_angle(self.theta);
+            let (a,b) = arc_coefficients(omega);
+            let norm = a*a + b*b;
+            ((a*self.x + b*self.y)/norm, (a*self.y - b*self.x)/norm, omega)
+        }
+
+        /// Adjoint matrix , maps a body velocity in the frame of self to the frame self is given in
+        /// (Ad * v = log(self ⊕ exp(v) ⊕ self^-1) for small v)
+        pub fn adjoint(&self)->JacobianModel2D<T>{
+            let (sin_t,cos_t) = self.theta.sin_cos();
+            JacobianModel2D::new([
+                [cos_t, -sin_t, self.y],
+                [sin_t, cos_t, -self.x],
+                [T::zero(), T::zero(), T::one()]
+            ])
+        }
+    }
+
+    /// a*b is the composition a ⊕ b
+    impl<T:Real> std::ops::Mul for Model2D<T>{
+        type Output = Model2D<T>;
+        fn mul(self, other:Model2D<T>)->Model2D<T>{
+            self.compose(other)
+        }
+    }
+
+    /// Jacobian of [integrate_body_motion] with respect to (forward, left, rotation) , rows x , y ,
+    /// theta. Continuous across the straight line like the motion itself
+    pub fn integrate_body_motion_jacobian<T:Real>(state:Model2D<T>, forward:T, left:T, rotation:T)->[[T;3];3]{
+        let (a,b) = arc_coefficients(rotation);
+        let (da,db) = arc_coefficient_derivatives(rotation);
+        let (sin_t,cos_t) = state.theta.sin_cos();
+        // columns in the frame of the start pose
+        let local = [(a,b),(-b,a),(da*forward - db*left, db*forward + da*left)];
+        let mut data = [[T::zero();3];3];
+        for (col,(dx,dy)) in local.iter().enumerate(){
+            data[0][col] = cos_t**dx - sin_t**dy;
+            data[1][col] = sin_t**dx + cos_t**dy;
+        }
+        data[2][2] = T::one();
+        data
+    }
+
+    /// How the differential drive models integrate a motion of length `distance` turning by
+    /// `angle` , the heading changes by the angle for all of them
+    #[derive(Copy,Clone,Debug,Default,PartialEq,Eq)]
+    pub enum Integrator{
+        /// Exact integration on the arc , see [integrate_body_motion]
+        #[default]
+        ExactArc,
+        /// Second order runge kutta , moves along the heading halfway through the turn
+        Midpoint,
+        /// First order , moves along the starting heading
+        Euler
+    }
+
+    impl Integrator{
+        pub fn integrate<T:Real>(&self, state:Model2D<T>, distance:T, angle:T)->Model2D<T>{
+            let course = match self{
+                Integrator::ExactArc=>return integrate_body_motion(state,distance,T::zero(),angle),
+                Integrator::Midpoint=>state.theta + angle/real(2.0),
+                Integrator::Euler=>state.theta
+            };
+            Model2D::new(state.x + distance*course.cos(), state.y + distance*course.sin(), state.theta + angle)
+        }
+
+        /// Jacobian of the new coordinates with respect to (x, y, theta)
+        pub fn state_jacobian<T:Real>(&self, state:Model2D<T>, distance:T, angle:T)->JacobianModel2D<T>{
+            let new = self.integrate(state,distance,angle);
+            let mut data = JacobianModel2D::identity();
+            let _ = data.column(2,(state.y - new.y, new.x - state.x, T::one()));
+            data
+        }
+
+        /// Jacobian of the new coordinates with respect to (distance, angle) , rows x , y , theta
+        pub fn motion_jacobian<T:Real>(&self, state:Model2D<T>, distance:T, angle:T)->[[T;2];3]{
+            let (sin_c,cos_c,half) = match self{
+                Integrator::ExactArc=>{
+                    let body = integrate_body_motion_jacobian(state,distance,T::zero(),angle);
+                    return [[body[0][0],body[0][2]],[body[1][0],body[1][2]],[T::zero(),T::one()]]
+                }
+                Integrator::Midpoint=>{
+                    let (sin_c,cos_c) = (state.theta + angle/real(2.0)).sin_cos();
+                    (sin_c,cos_c,distance/real(2.0))
+                }
+                Integrator::Euler=>{
+                    let (sin_c,cos_c) = state.theta.sin_cos();
+                    (sin_c,cos_c,T::zero())
+                }
+            };
+            [[cos_c,-half*sin_c],[sin_c,half*cos_c],[T::zero(),T::one()]]
+        }
+    }
+
+    /// A row or column index outside of a jacobian matrix , `len` is the number of rows or columns
+    #[derive(Copy,Clone,Debug,PartialEq,Eq)]
+    pub struct IndexOutOfRange{
+        pub index:usize,
+        pub len:usize
+    }
+
+    impl std::fmt::Display for IndexOutOfRange{
+        fn fmt(&self, f:&mut std::fmt::Formatter<'_>)->std::fmt::Result{
+            write!(f,"index {} out of range for length {}",self.index,self.len)
+        }
+    }
+
+    impl std::error::Error for IndexOutOfRange{}
+
+
     // TODO : DOCUMENT!!
     /// A 3x3 jacobian matrix for updating values 
     /// Mostly used in kalman filters
-    pub struct JacobianModel2D{
-        pub data:[[f32;3];3]
+    #[derive(Copy,Clone,Debug,PartialEq)]
+    pub struct JacobianModel2D<T:Real=f32>{
+        pub data:[[T;3];3]
     }
-    impl JacobianModel2D{
-        pub fn zeros()->JacobianModel2D{
+    impl<T:Real> JacobianModel2D<T>{
+        pub fn zeros()->JacobianModel2D<T>{
             JacobianModel2D{
-              data:[[0.0;3];3] 
+              data:[[T::zero();3];3] 
             }
         }
 
-        pub fn row(&mut self,index:usize, value:(f32,f32,f32))->Result<(),()>{
+        pub fn row(&mut self,index:usize, value:(T,T,T))->Result<(),IndexOutOfRange>{
             if index<3{
                 self.data[index][0] = value.0;
                 self.data[index][1] = value.1;
                 self.data[index][2] = value.2;
             }else{
-                return Err(())
+                return Err(IndexOutOfRange{index,len:3})
             }
             Ok(()) 
         }
 
-        pub fn column(&mut self,index:usize, value:(f32,f32,f32))->Result<(),()>{
+        pub fn column(&mut self,index:usize, value:(T,T,T))->Result<(),IndexOutOfRange>{
             if index<3{
                 self.data[0][index] = value.0;
                 self.data[1][index] = value.1;
                 self.data[2][index] = value.2;
             }else{
-                return Err(())
+                return Err(IndexOutOfRange{index,len:3})
             }
             Ok(()) 
         }
-        
-        
-        
+    }
+
+
+    /// Matrix algebra, enough to write `G*P*G.transpose() + V*M*V.transpose()` without an external
+    /// linear algebra library
+    impl<T:Real> JacobianModel2D<T>{
+        pub fn new(data:[[T;3];3])->JacobianModel2D<T>{
+            JacobianModel2D{
+                data
+            }
+        }
+
+        pub fn identity()->JacobianModel2D<T>{
+            let mut data = [[T::zero();3];3];
+            for (i,row) in data.iter_mut().enumerate(){
+                row[i] = T::one();
+            }
+            JacobianModel2D{
+                data
+            }
+        }
+
+        pub fn transpose(&self)->JacobianModel2D<T>{
+            let mut data = [[T::zero();3];3];
+            for (i,row) in data.iter_mut().enumerate(){
+                for (j,value) in row.iter_mut().enumerate(){
+                    *value = self.data[j][i];
+                }
+            }
+            JacobianModel2D{
+                data
+            }
+        }
+
+        pub fn determinant(&self)->T{
+            let m = &self.data;
+            m[0][0]*(m[1][1]*m[2][2] - m[1][2]*m[2][1])
+                - m[0][1]*(m[1][0]*m[2][2] - m[1][2]*m[2][0])
+                + m[0][2]*(m[1][0]*m[2][1] - m[1][1]*m[2][0])
+        }
+
+        /// Returns None if the matrix is singular
+        pub fn inverse(&self)->Option<JacobianModel2D<T>>{
+            let det = self.determinant();
+            if det==T::zero() || !det.is_finite(){
+                return None
+            }
+            let m = &self.data;
+            // transpose of the cofactor matrix
+            let adjugate = [
+                [m[1][1]*m[2][2] - m[1][2]*m[2][1], m[0][2]*m[2][1] - m[0][1]*m[2][2], m[0][1]*m[1][2] - m[0][2]*m[1][1]],
+                [m[1][2]*m[2][0] - m[1][0]*m[2][2], m[0][0]*m[2][2] - m[0][2]*m[2][0], m[0][2]*m[1][0] - m[0][0]*m[1][2]],
+                [m[1][0]*m[2][1] - m[1][1]*m[2][0], m[0][1]*m[2][0] - m[0][0]*m[2][1], m[0][0]*m[1][1] - m[0][1]*m[1][0]],
+            ];
+            let mut data = [[T::zero();3];3];
+            for (i,row) in data.iter_mut().enumerate(){
+                for (j,value) in row.iter_mut().enumerate(){
+                    *value = adjugate[i][j]/det;
+                }
+            }
+            Some(JacobianModel2D{
+                data
+            })
+        }
+
+        /// Multiplies the matrix with the column vector `v`
+        pub fn mul_vector(&self, v:(T,T,T))->(T,T,T){
+            let m = &self.data;
+            (
+                m[0][0]*v.0 + m[0][1]*v.1 + m[0][2]*v.2,
+                m[1][0]*v.0 + m[1][1]*v.1 + m[1][2]*v.2,
+                m[2][0]*v.0 + m[2][1]*v.1 + m[2][2]*v.2
+            )
+        }
+    }
+
+    impl<T:Real> std::ops::Add for JacobianModel2D<T>{
+        type Output = JacobianModel2D<T>;
+        fn add(self, other:JacobianModel2D<T>)->JacobianModel2D<T>{
+            let mut data = self.data;
+            for (row,other_row) in data.iter_mut().zip(other.data.iter()){
+                for (value,other_value) in row.iter_mut().zip(other_row.iter()){
+                    *value += *other_value;
+                }
+            }
+            JacobianModel2D{
+                data
+            }
+        }
+    }
+
+    impl<T:Real> std::ops::Sub for JacobianModel2D<T>{
+        type Output = JacobianModel2D<T>;
+        fn sub(self, other:JacobianModel2D<T>)->JacobianModel2D<T>{
+            self + other*(-T::one())
+        }
+    }
+
+    impl<T:Real> std::ops::Mul for JacobianModel2D<T>{
+        type Output = JacobianModel2D<T>;
+        fn mul(self, other:JacobianModel2D<T>)->JacobianModel2D<T>{
+            let mut data = [[T::zero();3];3];
+            for (i,row) in data.iter_mut().enumerate(){
+                for (j,value) in row.iter_mut().enumerate(){
+                    *value = (0..3).map(|k| self.data[i][k]*other.data[k][j]).sum();
+                }
+            }
+            JacobianModel2D{
+                data
+            }
+        }
+    }
+
+    impl<T:Real> std::ops::Mul<T> for JacobianModel2D<T>{
+        type Output = JacobianModel2D<T>;
+        fn mul(self, scale:T)->JacobianModel2D<T>{
+            let mut data = self.data;
+            data.iter_mut().for_each(|row| row.iter_mut().for_each(|value| *value *= scale));
+            JacobianModel2D{
+                data
+            }
+        }
+    }
+
+    impl<T:Real> std::ops::Mul<Covariance2D<T>> for JacobianModel2D<T>{
+        type Output = JacobianModel2D<T>;
+        fn mul(self, other:Covariance2D<T>)->JacobianModel2D<T>{
+            self*other.to_matrix()
+        }
+    }
+
+
+
+    /// A symmetric 3x3 covariance matrix of a [Model2D]
+    /// Only matrices that went through [Covariance2D::from_matrix] (or the constructors) are
+    /// stored, which keeps the matrix symmetric
+    #[derive(Copy,Clone,Debug,PartialEq)]
+    pub struct Covariance2D<T:Real=f32>{
+        data:[[T;3];3]
+    }
+
+    impl<T:Real> Covariance2D<T>{
+        pub fn zeros()->Covariance2D<T>{
+            Covariance2D{
+                data:[[T::zero();3];3]
+            }
+        }
+
+        pub fn diagonal(var_x:T,var_y:T,var_theta:T)->Covariance2D<T>{
+            let mut data = [[T::zero();3];3];
+            data[0][0] = var_x;
+            data[1][1] = var_y;
+            data[2][2] = var_theta;
+            Covariance2D{
+                data
+            }
+        }
+
+        /// Symmetrizes the matrix as (m + m^T)/2
+        pub fn from_matrix(m:JacobianModel2D<T>)->Covariance2D<T>{
+            Covariance2D{
+                data:((m + m.transpose())*real::<T>(0.5)).data
+            }
+        }
+
+        pub fn to_matrix(&self)->JacobianModel2D<T>{
+            JacobianModel2D::new(self.data)
+        }
+
+        pub fn get(&self,row:usize,column:usize)->T{
+            self.data[row][column]
+        }
+
+        pub fn determinant(&self)->T{
+            self.to_matrix().determinant()
+        }
+
+        /// Returns None if the matrix is singular
+        pub fn inverse(&self)->Option<Covariance2D<T>>{
+            self.to_matrix().inverse().map(Covariance2D::from_matrix)
+        }
+
+        /// Computes G * P * G^T
+        pub fn propagate(&self, g:&JacobianModel2D<T>)->Covariance2D<T>{
+            Covariance2D::from_matrix(*g*self.to_matrix()*g.transpose())
+        }
+    }
+
+    impl<T:Real> std::ops::Add for Covariance2D<T>{
+        type Output = Covariance2D<T>;
+        fn add(self, other:Covariance2D<T>)->Covariance2D<T>{
+            Covariance2D{
+                data:(self.to_matrix() + other.to_matrix()).data
+            }
+        }
+    }
+
+    impl<T:Real> std::ops::Mul<JacobianModel2D<T>> for Covariance2D<T>{
+        type Output = JacobianModel2D<T>;
+        fn mul(self, other:JacobianModel2D<T>)->JacobianModel2D<T>{
+            self.to_matrix()*other
+        }
+    }
+
+    impl<T:Real> From<Covariance2D<T>> for JacobianModel2D<T>{
+        fn from(cov:Covariance2D<T>)->JacobianModel2D<T>{
+            cov.to_matrix()
+        }
+    }
+
+    impl<T:Real> From<JacobianModel2D<T>> for Covariance2D<T>{
+        fn from(m:JacobianModel2D<T>)->Covariance2D<T>{
+            Covariance2D::from_matrix(m)
+        }
+    }
+
+
+
+    /// A 2x2 covariance matrix of the wheel increments (dl, dr)
+    #[derive(Copy,Clone,Debug,PartialEq)]
+    pub struct ControlCovariance2D<T:Real=f32>{
+        pub data:[[T;2];2]
+    }
+
+    impl<T:Real> ControlCovariance2D<T>{
+        pub fn diagonal(var_l:T,var_r:T)->ControlCovariance2D<T>{
+            ControlCovariance2D{
+                data:[[var_l,T::zero()],[T::zero(),var_r]]
+            }
+        }
+    }
+
+    /// A 3x2 jacobian matrix of the pose with respect to the wheel increments (dl, dr)
+    /// Used to map the wheel noise into the state covariance in kalman filters
+    #[derive(Copy,Clone,Debug,PartialEq)]
+    pub struct ControlJacobianModel2D<T:Real=f32>{
+        pub data:[[T;2];3]
+    }
+    impl<T:Real> ControlJacobianModel2D<T>{
+        pub fn zeros()->ControlJacobianModel2D<T>{
+            ControlJacobianModel2D{
+              data:[[T::zero();2];3]
+            }
+        }
+
+        pub fn row(&mut self,index:usize, value:(T,T))->Result<(),IndexOutOfRange>{
+            if index<3{
+                self.data[index][0] = value.0;
+                self.data[index][1] = value.1;
+            }else{
+                return Err(IndexOutOfRange{index,len:3})
+            }
+            Ok(())
+        }
+
+        pub fn column(&mut self,index:usize, value:(T,T,T))->Result<(),IndexOutOfRange>{
+            if index<2{
+                self.data[0][index] = value.0;
+                self.data[1][index] = value.1;
+                self.data[2][index] = value.2;
+            }else{
+                return Err(IndexOutOfRange{index,len:2})
+            }
+            Ok(())
+        }
+    }
+
+    impl<T:Real> ControlJacobianModel2D<T>{
+        /// Transpose as a 2x3 matrix
+        pub fn transpose(&self)->ControlJacobianTranspose2D<T>{
+            let mut data = [[T::zero();3];2];
+            for (i,row) in data.iter_mut().enumerate(){
+                for (j,value) in row.iter_mut().enumerate(){
+                    *value = self.data[j][i];
+                }
+            }
+            ControlJacobianTranspose2D{
+                data
+            }
+        }
+
+        /// Computes V * M * V^T
+        pub fn propagate(&self, m:&ControlCovariance2D<T>)->Covariance2D<T>{
+            Covariance2D::from_matrix(*self*(*m)*self.transpose())
+        }
+    }
+
+    impl<T:Real> std::ops::Mul<ControlCovariance2D<T>> for ControlJacobianModel2D<T>{
+        type Output = ControlJacobianModel2D<T>;
+        fn mul(self, other:ControlCovariance2D<T>)->ControlJacobianModel2D<T>{
+            let mut data = [[T::zero();2];3];
+            for (i,row) in data.iter_mut().enumerate(){
+                for (j,value) in row.iter_mut().enumerate(){
+                    *value = self.data[i][0]*other.data[0][j] + self.data[i][1]*other.data[1][j];
+                }
+            }
+            ControlJacobianModel2D{
+                data
+            }
+        }
+    }
+
+    impl<T:Real> std::ops::Mul<ControlJacobianTranspose2D<T>> for ControlJacobianModel2D<T>{
+        type Output = JacobianModel2D<T>;
+        fn mul(self, other:ControlJacobianTranspose2D<T>)->JacobianModel2D<T>{
+            let mut data = [[T::zero();3];3];
+            for (i,row) in data.iter_mut().enumerate(){
+                for (j,value) in row.iter_mut().enumerate(){
+                    *value = self.data[i][0]*other.data[0][j] + self.data[i][1]*other.data[1][j];
+                }
+            }
+            JacobianModel2D{
+                data
+            }
+        }
+    }
+
+
+    /// The 2x3 transpose of a [ControlJacobianModel2D]
+    #[derive(Copy,Clone,Debug,PartialEq)]
+    pub struct ControlJacobianTranspose2D<T:Real=f32>{
+        pub data:[[T;3];2]
     }
 
 
@@ -71,30 +682,92 @@ pub mod base{
     /// All motion models in 2 dimensions employ this trait, which takes int odometry information
     /// (Total DISTANCE travelled by the wheels (angle*radius)) and gives the new coordinates as
     /// output 
-    pub trait MotionUpdate2D{
-        fn update_coords_odometry(&mut self, odom_l:f32, odom_r:f32)->Model2D;
-        fn update_coords_odometry_stateless(&mut self, pos:Model2D,odom_l:f32,odom_r:f32)->Model2D;
-        fn get_jacobian_stateless(&mut self, pos:Model2D, odom_l:f32, odom_r:f32)->JacobianModel2D;
+    /// The meaning of the readings (odom_l, odom_r) depends on the model , see the documentation
+    /// of each implementation. Most models take cumulative readings and subtract the ones stored
+    /// by the previous update , the [crate::velocity_motion_model::VelocityMotionModel] takes the
+    /// wheel increments of one time step and the [crate::bicycle_motion_model::BicycleModel] the
+    /// inputs of one time step. A filter generic over this trait must be fed the readings the
+    /// model expects
+    pub trait MotionUpdate2D<T:Real=f32>{
+        fn update_coords_odometry(&mut self, odom_l:T, odom_r:T)->Model2D<T>;
+        fn update_coords_odometry_stateless(&mut self, pos:Model2D<T>,odom_l:T,odom_r:T)->Model2D<T>;
+        fn get_jacobian_stateless(&mut self, pos:Model2D<T>, odom_l:T, odom_r:T)->JacobianModel2D<T>;
+        /// Jacobian of the new coordinates with respect to the increments of the readings
+        /// (odom_l, odom_r) , the wheel increments (dl, dr) for the differential drive models.
+        /// The control covariance of a filter is in the same order and units
+        fn get_control_jacobian_stateless(&mut self, pos:Model2D<T>, odom_l:T, odom_r:T)->ControlJacobianModel2D<T>;
     }
 
 
 
-pub struct ChangeParams{
-    pub R:f32,
-    pub alpha:f32,
-    pub s:f32
-}
-impl ChangeParams{
-    pub fn new(R:f32,alpha:f32,s:f32)->ChangeParams{
-        ChangeParams{
-            R,
-            alpha,
-            s
+    /// Radius of turning R , angle of turn alpha and distance travelled s of a motion increment.
+    /// The [crate::velocity_motion_model::VelocityMotionModel] stores the rates instead , omega in
+    /// alpha and v in s , multiplied by its time step they are the increments. R is zero unless the
+    /// motion is an [MotionIncrement::Arc]
+    #[allow(non_snake_case)]
+    #[derive(Copy,Clone,Debug,PartialEq)]
+    pub struct ChangeParams<T:Real=f32>{
+        pub R:T,
+        pub alpha:T,
+        pub s:T
+    }
+    impl<T:Real> ChangeParams<T>{
+        #[allow(non_snake_case)]
+        pub fn new(R:T,alpha:T,s:T)->ChangeParams<T>{
+            ChangeParams{
+                R,
+                alpha,
+                s
+            }
         }
     }
-}
 
 
+    /// The motion between two odometry readings
+    #[derive(Copy,Clone,Debug,PartialEq)]
+    pub enum MotionIncrement<T:Real=f32>{
+        /// Neither moved nor turned
+        Stationary,
+        /// Moved without turning , only s is set
+        Straight(ChangeParams<T>),
+        /// Moved on an arc of radius R
+        Arc(ChangeParams<T>),
+        /// Turned without moving , the wheels moved by opposite distances , only alpha is set
+        RotateInPlace(ChangeParams<T>)
+    }
+
+    impl<T:Real> MotionIncrement<T>{
+        /// Classifies the motion with angle of turn alpha and distance s. `turn_length` converts the
+        /// turn into the distance it moves the wheels , half the base length of a differential
+        /// drive , so |s| + |alpha|*turn_length is the largest wheel increment.
+        /// A part smaller than the rounding error of that increment counts as zero , the readings
+        /// of two wheels rarely cancel exactly
+        pub fn new(alpha:T, s:T, turn_length:T)->MotionIncrement<T>{
+            let turn = alpha.abs()*turn_length;
+            let tolerance = real::<T>(4.0)*T::epsilon()*(s.abs() + turn);
+            match (turn<=tolerance,s.abs()<=tolerance){
+                (true,true)=>MotionIncrement::Stationary,
+                (true,false)=>MotionIncrement::Straight(ChangeParams::new(T::zero(),T::zero(),s)),
+                (false,true)=>MotionIncrement::RotateInPlace(ChangeParams::new(T::zero(),alpha,T::zero())),
+                // radius of the arc travelled by the center of the axle
+                (false,false)=>MotionIncrement::Arc(ChangeParams::new(s/alpha,alpha,s))
+            }
+        }
+
+        /// The parameters of the motion , all zero if stationary
+        pub fn change(&self)->ChangeParams<T>{
+            match self{
+                MotionIncrement::Stationary=>ChangeParams::new(T::zero(),T::zero(),T::zero()),
+                MotionIncrement::Straight(change)|MotionIncrement::Arc(change)|MotionIncrement::RotateInPlace(change)=>*change
+            }
+        }
+
+        /// True if the heading does not change , stationary or straight
+        pub fn is_straight(&self)->bool{
+            matches!(self,MotionIncrement::Stationary|MotionIncrement::Straight(_))
+        }
+    }
+
 
 
 
@@ -107,4 +780,69 @@ mod testsinternal {
     fn it_works() {
         assert_eq!(2 + 2, 4);
     }
+
+    #[test]
+    fn se2_test(){
+        use super::base::Model2D;
+        let a = Model2D::new(1.0f64,2.0,0.5);
+        let b = Model2D::new(-0.3,0.7,-2.0);
+        let close = |p:Model2D<f64>,q:Model2D<f64>| (p.x-q.x).abs()<1e-12 && (p.y-q.y).abs()<1e-12
+            && super::base::normalize_angle(p.theta-q.theta).abs()<1e-12;
+
+        assert!(close(a*a.inverse(),Model2D::identity()));
+        assert!(close(a.inverse()*a,Model2D::identity()));
+        assert!(close(a*(b*a),(a*b)*a));
+        assert!(close(b*a.relative_to(b),a));
+        assert!(close(a.relative_to(a),Model2D::identity()));
+        // a quarter turn then one step forward ends to the left
+        assert!(close(Model2D::new(0.0,0.0,std::f64::consts::FRAC_PI_2)*Model2D::new(1.0,0.0,0.0),Model2D::new(0.0,1.0,std::f64::consts::FRAC_PI_2)));
+
+        // exp and log are inverse , also for tiny rotations
+        for (vx,vy,omega) in [(0.3f64,-0.2,1.2),(1.0,0.5,1e-9),(0.0,0.0,-3.0),(2.0,0.0,0.0)].iter(){
+            let (x,y,w) = Model2D::exp(*vx,*vy,*omega).log();
+            assert!((x-vx).abs()<1e-12 && (y-vy).abs()<1e-12 && (w-omega).abs()<1e-12);
+        }
+        assert!(close(Model2D::exp(a.log().0,a.log().1,a.log().2),a));
+        assert!(close(Model2D::new(0.0,0.0,7.0).normalized(),Model2D::new(0.0,0.0,7.0-2.0*std::f64::consts::PI)));
+
+        // the adjoint moves a small motion of the body frame to the world frame
+        let h = 1e-6;
+        let v = (0.4*h,-0.7*h,0.9*h);
+        let (x,y,w) = (a*Model2D::exp(v.0,v.1,v.2)*a.inverse()).log();
+        let (ax,ay,aw) = a.adjoint().mul_vector(v);
+        assert!((x-ax).abs()<1e-12 && (y-ay).abs()<1e-12 && (w-aw).abs()<1e-12);
+    }
+
+    #[test]
+    fn jacobian_algebra_test(){
+        use super::base::{JacobianModel2D,Covariance2D,ControlJacobianModel2D,ControlCovariance2D};
+        let g:JacobianModel2D = JacobianModel2D::new([[1.0,0.0,-0.2],[0.0,1.0,0.3],[0.0,0.0,1.0]]);
+        assert!((g.determinant()-1.0).abs()<1e-6);
+        let product = g*g.inverse().unwrap();
+        for (i,row) in product.data.iter().enumerate(){
+            for (j,value) in row.iter().enumerate(){
+                let expected = if i==j { 1.0 } else { 0.0 };
+                assert!((value-expected).abs()<1e-6);
+            }
+        }
+        assert!(JacobianModel2D::<f32>::zeros().inverse().is_none());
+        assert_eq!(g.transpose().transpose(),g);
+        assert_eq!(g - g,JacobianModel2D::zeros());
+
+        let p = Covariance2D::diagonal(0.1,0.2,0.3);
+        let mut v = ControlJacobianModel2D::zeros();
+        let _ = v.row(0,(0.5,0.5));
+        let _ = v.row(2,(-10.0,10.0));
+        let m = ControlCovariance2D::diagonal(0.01,0.01);
+
+        let written_out = Covariance2D::from_matrix(g*p*g.transpose() + v*m*v.transpose());
+        let propagated = p.propagate(&g) + v.propagate(&m);
+        assert_eq!(written_out,propagated);
+        assert!((propagated.get(0,2)-propagated.get(2,0)).abs()<1e-9);
+        assert!((propagated.get(2,2)-(0.3+2.0)).abs()<1e-5);
+
+        let mut w = ControlJacobianModel2D::<f32>::zeros();
+        assert_eq!(w.column(2,(1.0,1.0,1.0)),Err(super::base::IndexOutOfRange{index:2,len:2}));
+        assert_eq!(JacobianModel2D::<f32>::zeros().row(3,(1.0,1.0,1.0)).unwrap_err().to_string(),"index 3 out of range for length 3");
+    }
 }
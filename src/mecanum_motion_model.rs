@@ -0,0 +1,159 @@
+use crate::base;
+
+
+/// Number of wheels of a mecanum base
+pub const WHEELS:usize = 4;
+
+
+/// A holonomic base with four mecanum wheels in X configuration (the rollers touching the ground
+/// form an X seen from above). The wheels are ordered front left , front right , rear left , rear
+/// right. `half_length` and `half_width` are the distances from the center to the wheel axles and
+/// to the wheel centers.
+/// `odometry[i]` represents the DISTANCE covered by the wheel , wheel radius * wheel angle , and like
+/// [crate::odometry_motion_model::OdometryModel] the readings are cumulative
+#[derive(Clone)]
+pub struct MecanumModel<T:base::Real=f32>{
+    odometry:[T;WHEELS],
+    pub x_t:base::Model2D<T>,
+    half_length:T,
+    half_width:T
+}
+
+impl<T:base::Real> MecanumModel<T>{
+
+    pub fn new(half_length:T, half_width:T)->MecanumModel<T>{
+        MecanumModel{
+            odometry:[T::zero();WHEELS],
+            x_t:base::Model2D::new(T::zero(),T::zero(),T::zero()),
+            half_length,
+            half_width
+        }
+    }
+
+
+    /// Forward kinematics , wheel speeds to the body twist (vx, vy, omega) in the robot frame.
+    /// Wheel distances give the body displacement (forward, left, rotation) the same way
+    pub fn body_twist(&self, wheels:[T;WHEELS])->(T,T,T){
+        let [fl,fr,rl,rr] = wheels;
+        let four = base::real::<T>(4.0);
+        (
+            (fl + fr + rl + rr)/four,
+            (-fl + fr + rl - rr)/four,
+            (-fl + fr - rl + rr)/(four*(self.half_length + self.half_width))
+        )
+    }
+
+
+    /// Inverse kinematics , the wheel speeds for the body twist (vx, vy, omega)
+    pub fn wheel_speeds(&self, vx:T, vy:T, omega:T)->[T;WHEELS]{
+        let turn = (self.half_length + self.half_width)*omega;
+        [vx - vy - turn, vx + vy + turn, vx + vy - turn, vx - vy + turn]
+    }
+
+
+    /// Wheel distances travelled since the stored readings
+    fn increments(&self, odometry:[T;WHEELS])->[T;WHEELS]{
+        let mut out = odometry;
+        for (value,previous) in out.iter_mut().zip(self.odometry.iter()){
+            *value -= *previous;
+        }
+        out
+    }
+
+
+    pub fn update_odometry_readings(&mut self, odometry:[T;WHEELS]){
+        self.odometry = odometry;
+    }
+
+
+    /// New coordinates after the wheels covered the distances `increments`
+    pub fn update_coords_stateless(&self, state:base::Model2D<T>, increments:[T;WHEELS])->base::Model2D<T>{
+        let (forward,left,rotation) = self.body_twist(increments);
+        base::integrate_body_motion(state,forward,left,rotation)
+    }
+
+
+    /// Gets the jacobian of the new coordinates with respect to (x, y, theta) , the wheels having
+    /// covered the distances `increments`
+    pub fn update_get_jacobian_stateless(&self, state:base::Model2D<T>, increments:[T;WHEELS])->base::JacobianModel2D<T>{
+        let new = self.update_coords_stateless(state,increments);
+        let mut data = base::JacobianModel2D::identity();
+        let _ = data.column(2,(state.y - new.y, new.x - state.x, T::one()));
+        data
+    }
+
+
+    /// Updates the pose with cumulative wheel distances and stores the readings
+    pub fn update_coords_odometry(&mut self, odometry:[T;WHEELS])->base::Model2D<T>{
+        self.x_t = self.update_coords_stateless(self.x_t,self.increments(odometry));
+        self.update_odometry_readings(odometry);
+        self.x_t
+    }
+
+
+    /// Updates the pose with wheel speeds held for `time_step` , the stored readings are not used
+    pub fn update_coords_velocity(&mut self, speeds:[T;WHEELS], time_step:T)->base::Model2D<T>{
+        let mut increments = speeds;
+        increments.iter_mut().for_each(|v| *v *= time_step);
+        self.x_t = self.update_coords_stateless(self.x_t,increments);
+        self.x_t
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn mecanum_kinematics_test(){
+        let model = super::MecanumModel::new(0.2f64,0.15);
+        // all wheels forward , a strafe to the left and a rotation in place
+        assert_eq!(model.body_twist([1.0,1.0,1.0,1.0]),(1.0,0.0,0.0));
+        assert_eq!(model.body_twist([-1.0,1.0,1.0,-1.0]),(0.0,1.0,0.0));
+        let (vx,vy,omega) = model.body_twist([-0.35,0.35,-0.35,0.35]);
+        assert!(vx==0.0 && vy==0.0 && (omega-1.0).abs()<1e-12);
+
+        let wheels = model.wheel_speeds(0.3,-0.2,0.7);
+        let (vx,vy,omega) = model.body_twist(wheels);
+        assert!((vx-0.3).abs()<1e-12 && (vy+0.2).abs()<1e-12 && (omega-0.7).abs()<1e-12);
+    }
+
+    #[test]
+    fn mecanum_integration_test(){
+        // one exact step equals many small steps of the same twist
+        let mut exact = super::MecanumModel::new(0.2f64,0.15);
+        let mut fine = exact.clone();
+        exact.x_t = crate::base::Model2D::new(1.0,2.0,0.3);
+        fine.x_t = exact.x_t;
+        let speeds = exact.wheel_speeds(0.5,0.2,0.8);
+        let pos = exact.update_coords_velocity(speeds,1.0);
+        for _ in 0..10000{
+            fine.update_coords_velocity(speeds,1e-4);
+        }
+        assert!((pos.x-fine.x_t.x).abs()<1e-9 && (pos.y-fine.x_t.y).abs()<1e-9 && (pos.theta-fine.x_t.theta).abs()<1e-9);
+
+        // cumulative readings , a strafe of 0.5 to the left of the heading
+        let mut model = super::MecanumModel::new(0.2f64,0.15);
+        model.x_t = crate::base::Model2D::new(0.0,0.0,std::f64::consts::FRAC_PI_2);
+        let pos = model.update_coords_odometry([-0.5,0.5,0.5,-0.5]);
+        assert!((pos.x+0.5).abs()<1e-12 && pos.y.abs()<1e-12);
+        let pos = model.update_coords_odometry([-0.5,0.5,0.5,-0.5]);
+        assert!((pos.x+0.5).abs()<1e-12);
+
+        // tiny rotations keep the lateral drift
+        let pos = crate::base::integrate_body_motion(crate::base::Model2D::new(0.0f32,0.0,0.0),1.0,0.0,1e-7);
+        assert!((pos.y-0.5e-7).abs()<1e-12);
+    }
+
+    #[test]
+    fn jacobian_mecanum_model_test(){
+        let model = super::MecanumModel::new(0.2f64,0.15);
+        let state = crate::base::Model2D::new(0.4,-0.3,1.1);
+        let increments = [0.1,0.3,-0.05,0.2];
+        let g = model.update_get_jacobian_stateless(state,increments);
+        let update = |p:[f64;3]| model.update_coords_stateless(crate::base::Model2D::new(p[0],p[1],p[2]),increments);
+        crate::tests::assert_numeric_jacobian(&g.data,update,[state.x,state.y,state.theta],1e-6,1e-8);
+        assert_eq!(g.data[0][0],1.0);
+    }
+}
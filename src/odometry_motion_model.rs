@@ -1,16 +1,30 @@
 use super::base;
-
-pub struct ChangeParams{
-    pub R:f32,
-    pub alpha:f32,
-    pub s:f32
+pub use crate::base::{ChangeParams,MotionIncrement};
+use crate::probability;
+use rand::Rng;
+
+
+
+/// Noise parameters of the sampling odometry motion model
+/// (Probabilistic Robotics, Thrun et al. , table 5.6)
+/// alpha1 : rotation noise caused by rotation
+/// alpha2 : rotation noise caused by translation
+/// alpha3 : translation noise caused by translation
+/// alpha4 : translation noise caused by rotation
+#[derive(Copy,Clone,Debug)]
+pub struct OdometryNoise<T:base::Real=f32>{
+    pub alpha1:T,
+    pub alpha2:T,
+    pub alpha3:T,
+    pub alpha4:T
 }
-impl ChangeParams{
-    pub fn new(R:f32,alpha:f32,s:f32)->ChangeParams{
-        ChangeParams{
-            R,
-            alpha,
-            s
+impl<T:base::Real> OdometryNoise<T>{
+    pub fn new(alpha1:T,alpha2:T,alpha3:T,alpha4:T)->OdometryNoise<T>{
+        OdometryNoise{
+            alpha1,
+            alpha2,
+            alpha3,
+            alpha4
         }
     }
 }
@@ -21,65 +35,91 @@ impl ChangeParams{
 /// odometry_l and odometry_r represent the DISTANCE covered by the wheel 
 /// odometry_l is NOT the encoder reading you obtain using a rotation sensor 
 /// rather , it is Radius_of_wheel * angle_moved_by_wheel
-pub struct OdometryModel{
-    odometry_l:f32,
-    odometry_r:f32,
-    pub x_t:base::Model2D,
-    x_tprev:base::Model2D,
-    base_length:f32
+#[derive(Clone)]
+pub struct OdometryModel<T:base::Real=f32>{
+    odometry_l:T,
+    odometry_r:T,
+    pub x_t:base::Model2D<T>,
+    x_tprev:base::Model2D<T>,
+    base_length:T,
+    heading:base::HeadingTracker,
+    integrator:base::Integrator
 }
 
-impl OdometryModel{
+impl<T:base::Real> OdometryModel<T>{
     
-    pub fn new(base_length:f32)->OdometryModel{
+    pub fn new(base_length:T)->OdometryModel<T>{
+        Self::with_heading_policy(base_length,base::HeadingPolicy::default())
+    }
+
+    /// The heading of the updated poses follows `policy` , [OdometryModel::new] keeps it continuous
+    pub fn with_heading_policy(base_length:T, policy:base::HeadingPolicy)->OdometryModel<T>{
         OdometryModel{
-            odometry_l:0.0,
-            odometry_r:0.0,
-            x_t:base::Model2D::new(0.0,0.0,0.0),
-            x_tprev:base::Model2D::new(0.0,0.0,0.0),
+            odometry_l:T::zero(),
+            odometry_r:T::zero(),
+            x_t:base::Model2D::new(T::zero(),T::zero(),T::zero()),
+            x_tprev:base::Model2D::new(T::zero(),T::zero(),T::zero()),
             base_length,
+            heading:base::HeadingTracker::new(policy),
+            integrator:base::Integrator::default()
         }
     }
 
+    pub fn heading_policy(&self)->base::HeadingPolicy{
+        self.heading.policy
+    }
+
+    pub fn integrator(&self)->base::Integrator{
+        self.integrator
+    }
+
+    /// Selects how the updates and jacobians of the model integrate the motion , the exact arc by
+    /// default. The associated `_stateless` functions without `self` always use the exact arc
+    pub fn set_integrator(&mut self, integrator:base::Integrator){
+        self.integrator = integrator;
+    }
 
-    /// Returns radius of turning , angle of turn and distance travelled 
-    /// If motion is in a straight line , returns Error(distancetravelled)
+    /// Heading of x_t before wrapping , the sum of all rotations of the stateful updates
+    pub fn unwrapped_heading(&self)->T{
+        self.heading.unwrapped(self.x_t.theta)
+    }
+
+    /// Number of complete turns of [OdometryModel::unwrapped_heading] , positive counter clockwise
+    pub fn turns(&self)->i64{
+        self.heading.turns(self.x_t.theta)
+    }
+
+
+    /// Returns radius of turning , angle of turn and distance travelled , classified as
+    /// stationary , straight , arc or rotation in place (see [MotionIncrement])
     /// As stated in the struct definition odometry_l represents the DISTANCE covered by the wheel
     /// This function does not affect any value of the state of the differential robot model.
     /// If you intend to update the odometry motion model use the function 
     /// `update_odometry_readings()`
-    pub fn update_get_radius_angle_distance(&mut self, odometry_l:f32,odometry_r:f32)->Result<ChangeParams,ChangeParams>{
-        let L = self.base_length;
+    pub fn update_get_radius_angle_distance(&mut self, odometry_l:T,odometry_r:T)->MotionIncrement<T>{
+        let base_length = self.base_length;
         let diff_l =  odometry_l - self.odometry_l;
         let diff_r = odometry_r - self.odometry_r;
         
-        let alpha = (diff_r - diff_l)/L;
-        let delta_s = (diff_l+diff_r)/2.0;
-        
-        if alpha==0.0{
-            return Err(ChangeParams::new(0.0,0.0,delta_s))
-        }
-
-        let R = diff_l/alpha;
-        Ok(ChangeParams::new(R,alpha,delta_s)) 
+        let alpha = (diff_r - diff_l)/base_length;
+        let delta_s = (diff_l+diff_r)/base::real::<T>(2.0);
+        MotionIncrement::new(alpha,delta_s,base_length/base::real::<T>(2.0))
     }
     
     
     #[deprecated]
-    pub fn update_get_radius_angle_distance_depr(&mut self, odometry_l:f32,odometry_r:f32)->Result<ChangeParams,ChangeParams>{
-        let L = self.base_length;
+    pub fn update_get_radius_angle_distance_depr(&mut self, odometry_l:T,odometry_r:T)->MotionIncrement<T>{
+        let base_length = self.base_length;
         let diff_l =  odometry_l - self.odometry_l;
         let diff_r = odometry_r - self.odometry_r;
         
-        let alpha = (diff_r - diff_l)/(2.0*L);
-        let delta_s = (diff_l+diff_r)/2.0;
+        let alpha = (diff_r - diff_l)/(base::real::<T>(2.0)*base_length);
+        let delta_s = (diff_l+diff_r)/base::real::<T>(2.0);
         
-        if alpha==0.0{
-            return Err(ChangeParams::new(0.0,0.0,delta_s))
+        match MotionIncrement::new(alpha,delta_s,base_length){
+            MotionIncrement::Arc(_)=>MotionIncrement::Arc(ChangeParams::new(diff_l/alpha,alpha,delta_s)),
+            increment=>increment
         }
-
-        let R = diff_l/alpha;
-        Ok(ChangeParams::new(R,alpha,delta_s)) 
     }
 
 
@@ -89,17 +129,17 @@ impl OdometryModel{
     /// If you want a hassle free robot working,call this function
     /// The reasin why this is not called internally is because the functions that return the
     /// jacobian are not supposed to update state in any way
-    pub fn update_odometry_readings(&mut self,odometry_l:f32,odometry_r:f32){
+    pub fn update_odometry_readings(&mut self,odometry_l:T,odometry_r:T){
         self.odometry_l=odometry_l;
         self.odometry_r=odometry_r;
     }
     
 
     /// Updates position coordinates and returns the new position coordinates 
-    pub fn update_position_coords(&mut self,pos_change:ChangeParams)->base::Model2D{
-        let y_new = pos_change.R*self.x_t.theta.cos() - pos_change.R*(self.x_t.theta + pos_change.alpha).cos() + self.x_t.y;
-        let x_new = pos_change.R*(self.x_t.theta + pos_change.alpha).sin() - pos_change.R*self.x_t.theta.sin() + self.x_t.x;
-        let theta_new = self.x_t.theta + pos_change.alpha;
+    pub fn update_position_coords(&mut self,pos_change:ChangeParams<T>)->base::Model2D<T>{
+        let new = self.integrator.integrate(self.x_t,pos_change.s,pos_change.alpha);
+        let (x_new,y_new) = (new.x,new.y);
+        let theta_new = self.heading.update(new.theta);
         
         self.x_tprev.x = self.x_t.x;
         self.x_tprev.y = self.x_t.y;
@@ -115,11 +155,10 @@ impl OdometryModel{
     // TODO TEST 
     /// Updates position coordinates and returns the new position coordinates
     /// But stateless. You have to provide the inputs, useful for working with matrices 
-    pub fn update_position_coords_stateless(state:base::Model2D,pos_change:ChangeParams)->base::Model2D{
-        let y_new = pos_change.R*state.theta.cos() - pos_change.R*(state.theta + pos_change.alpha).cos() + state.y;
-        let x_new = pos_change.R*(state.theta + pos_change.alpha).sin() - pos_change.R*state.theta.sin() + state.x;
-        let theta_new = state.theta + pos_change.alpha;
-        base::Model2D::new(x_new,y_new, theta_new)
+    /// Only the distance and the angle of turn are used , not the radius which grows without
+    /// bound for tiny angles , so the result is accurate down to the straight line
+    pub fn update_position_coords_stateless(state:base::Model2D<T>,pos_change:ChangeParams<T>)->base::Model2D<T>{
+        base::integrate_body_motion(state,pos_change.s,T::zero(),pos_change.alpha)
     }
 
 
@@ -127,8 +166,9 @@ impl OdometryModel{
 
 
 
-    /// If the angle of curvature is zero update position_coords will not work
-    pub fn update_position_coords_straight_line(&mut self, distance:ChangeParams)->base::Model2D{
+    /// Motion in a straight line , the same as [OdometryModel::update_position_coords] with a zero
+    /// angle of turn
+    pub fn update_position_coords_straight_line(&mut self, distance:ChangeParams<T>)->base::Model2D<T>{
         let y_new = self.x_t.y + distance.s*self.x_t.theta.sin();
         let x_new = self.x_t.x + distance.s*self.x_t.theta.cos();
         
@@ -138,12 +178,14 @@ impl OdometryModel{
         
         self.x_t.x = x_new;
         self.x_t.y = y_new;
+        self.x_t.theta = self.heading.update(self.x_t.theta);
         base::Model2D::new(x_new,y_new,self.x_t.theta)
 
     }
 
-    /// If the angle of curvature is zero update_position_coords will not work
-    pub fn update_position_coords_straight_line_stateless(state:base::Model2D, distance:ChangeParams)->base::Model2D{
+    /// Motion in a straight line , the same as [OdometryModel::update_position_coords_stateless]
+    /// with a zero angle of turn
+    pub fn update_position_coords_straight_line_stateless(state:base::Model2D<T>, distance:ChangeParams<T>)->base::Model2D<T>{
         let y_new = state.y + distance.s*state.theta.sin();
         let x_new = state.x + distance.s*state.theta.cos();
         base::Model2D::new(x_new,y_new,state.theta)
@@ -153,60 +195,158 @@ impl OdometryModel{
     // TODO ADD DOCS!!
     // TODO test 
     /// Gets the jacobian of a function under normal conditions.
-    /// If motion is in a straight line the radius of curvature is infinity , because of which you
-    /// may  have to use  [OdometryModel::update_get_jacobian_straight_line_stateless]
-    pub fn update_get_jacobian_stateless(state:base::Model2D, pos_change:ChangeParams)->base::JacobianModel2D{
-        let mut data = base::JacobianModel2D::zeros();
-        let y_jacobian = -pos_change.R*state.theta.sin() + pos_change.R*(state.theta + pos_change.alpha).sin();
-        
-        let x_jacobian = pos_change.R*(state.theta + pos_change.alpha).cos() - pos_change.R*state.theta.cos();
+    /// Continuous as the angle of turn goes to zero , where it equals
+    /// [OdometryModel::update_get_jacobian_straight_line_stateless]
+    pub fn update_get_jacobian_stateless(state:base::Model2D<T>, pos_change:ChangeParams<T>)->base::JacobianModel2D<T>{
+        let mut data = base::JacobianModel2D::identity();
+        let new = Self::update_position_coords_stateless(state,pos_change);
+        let y_jacobian = new.x - state.x;
+        let x_jacobian = state.y - new.y;
 
-        let theta_jacobian = 0.0;
+        let theta_jacobian = T::one();
 
-        data.column(2,(x_jacobian,y_jacobian,theta_jacobian));
+        let _ = data.column(2,(x_jacobian,y_jacobian,theta_jacobian));
         data
     }
 
     
-    // TODO ADD DOCS!!
-    // TODO test 
-    pub fn update_get_jacobian_straight_line_stateless(state:base::Model2D, distance:ChangeParams)->base::JacobianModel2D{
-        let mut data = base::JacobianModel2D::zeros();
+    /// Gets the jacobian of a motion in a straight line , the limit of
+    /// [OdometryModel::update_get_jacobian_stateless] as the angle of turn goes to zero
+    pub fn update_get_jacobian_straight_line_stateless(state:base::Model2D<T>, distance:ChangeParams<T>)->base::JacobianModel2D<T>{
+        let mut data = base::JacobianModel2D::identity();
         let y_jacobian = distance.s*state.theta.cos();
         let x_jacobian = -distance.s*state.theta.sin();
-        
-        // FIXME DELETE
-        if cfg!(test)
-        {
-            println!("x_jacobian : {}, y_jacobian:{}",x_jacobian,y_jacobian);
-        }
 
-        let theta_jacobian = 0.0;
-        data.column(2,(x_jacobian,y_jacobian,theta_jacobian));
+        let theta_jacobian = T::one();
+        let _ = data.column(2,(x_jacobian,y_jacobian,theta_jacobian));
         data
     }
 
 
 
+    /// Gets the jacobian of the new coordinates with respect to the wheel increments (dl, dr)
+    /// under normal conditions. Continuous as the angle of turn goes to zero , where it equals
+    /// [OdometryModel::update_get_control_jacobian_straight_line_stateless]
+    pub fn update_get_control_jacobian_stateless(&self, state:base::Model2D<T>, pos_change:ChangeParams<T>)->base::ControlJacobianModel2D<T>{
+        wheel_control_jacobian(self.integrator,state,pos_change.s,pos_change.alpha,self.base_length)
+    }
+
+
+    /// Gets the jacobian of the new coordinates with respect to the wheel increments (dl, dr)
+    /// when moving in a straight line. This is the limit of the arc jacobian as the angle of turn
+    /// goes to zero, a difference between the wheels still turns the robot.
+    pub fn update_get_control_jacobian_straight_line_stateless(&self, state:base::Model2D<T>, distance:ChangeParams<T>)->base::ControlJacobianModel2D<T>{
+        wheel_control_jacobian(self.integrator,state,distance.s,T::zero(),self.base_length)
+    }
+
+
+
+    pub fn base_length(&self)->T{
+        self.base_length
+    }
+
 
 
     /// Converts an angle value to distance, the input is the angle data
-    pub fn angle_to_distance(angle_l:f32,angle_r:f32,wheel_radius:f32)->(f32,f32){
-        return (angle_l*wheel_radius,angle_r*wheel_radius)
+    pub fn angle_to_distance(angle_l:T,angle_r:T,wheel_radius:T)->(T,T){
+        (angle_l*wheel_radius,angle_r*wheel_radius)
+    }
+
+
+
+    /// Decomposes the motion between the last stored odometry readings and the given readings into
+    /// an initial rotation, a translation and a final rotation (rot1, trans, rot2).
+    /// Backward motion gives a negative translation instead of a rotation by pi.
+    /// Like `update_get_radius_angle_distance` this does not change the state of the model.
+    pub fn get_rot_trans_rot(&mut self, odometry_l:T,odometry_r:T)->(T,T,T){
+        let origin = base::Model2D::new(T::zero(),T::zero(),T::zero());
+        let end = Self::update_position_coords_stateless(origin,self.update_get_radius_angle_distance(odometry_l,odometry_r).change());
+        Self::rot_trans_rot_between(origin,end)
+    }
+
+
+    /// Decomposes the motion from `start` to `end` into (rot1, trans, rot2)
+    /// See [OdometryModel::get_rot_trans_rot]
+    pub fn rot_trans_rot_between(start:base::Model2D<T>, end:base::Model2D<T>)->(T,T,T){
+        let dx = end.x - start.x;
+        let dy = end.y - start.y;
+        let mut trans = (dx*dx + dy*dy).sqrt();
+        let mut rot1 = if trans > T::epsilon() { base::normalize_angle(dy.atan2(dx) - start.theta) } else { T::zero() };
+        if rot1 > T::FRAC_PI_2() {
+            rot1 -= T::PI();
+            trans = -trans;
+        }else if rot1 < -T::FRAC_PI_2() {
+            rot1 += T::PI();
+            trans = -trans;
+        }
+        let rot2 = end.theta - start.theta - rot1;
+        (rot1,trans,rot2)
+    }
+
+
+    /// Draws a noisy successor of `pos` given the new odometry readings
+    /// (sample_motion_model_odometry, Probabilistic Robotics table 5.6).
+    /// The odometry readings of the model are NOT updated, so the same readings can be used to
+    /// sample every particle of a filter. Call `update_odometry_readings()` once you are done.
+    pub fn sample_motion_model_odometry<R:Rng+?Sized>(&mut self, pos:base::Model2D<T>, odometry_l:T, odometry_r:T, noise:&OdometryNoise<T>, rng:&mut R)->base::Model2D<T>{
+        let (rot1,trans,rot2) = self.get_rot_trans_rot(odometry_l,odometry_r);
+
+        let rot1_hat = rot1 - probability::sample_normal(noise.alpha1*rot1*rot1 + noise.alpha2*trans*trans, rng);
+        let trans_hat = trans - probability::sample_normal(noise.alpha3*trans*trans + noise.alpha4*rot1*rot1 + noise.alpha4*rot2*rot2, rng);
+        let rot2_hat = rot2 - probability::sample_normal(noise.alpha1*rot2*rot2 + noise.alpha2*trans*trans, rng);
+
+        let x_new = pos.x + trans_hat*(pos.theta + rot1_hat).cos();
+        let y_new = pos.y + trans_hat*(pos.theta + rot1_hat).sin();
+        let theta_new = self.heading.policy.apply(pos.theta + rot1_hat + rot2_hat);
+        base::Model2D::new(x_new,y_new,theta_new)
+    }
+
+
+    /// Log of the density p(pos_end | odometry, pos_start) of the odometry motion model
+    /// (motion_model_odometry, Probabilistic Robotics table 5.5).
+    /// Like the sampling function this does not update the odometry readings of the model.
+    pub fn log_motion_model_odometry(&mut self, pos_start:base::Model2D<T>, pos_end:base::Model2D<T>, odometry_l:T, odometry_r:T, noise:&OdometryNoise<T>)->T{
+        let (rot1,trans,rot2) = self.get_rot_trans_rot(odometry_l,odometry_r);
+        let (rot1_hat,trans_hat,rot2_hat) = Self::rot_trans_rot_between(pos_start,pos_end);
+
+        let p1 = probability::log_prob_normal(base::normalize_angle(rot1 - rot1_hat), noise.alpha1*rot1_hat*rot1_hat + noise.alpha2*trans_hat*trans_hat);
+        let p2 = probability::log_prob_normal(trans - trans_hat, noise.alpha3*trans_hat*trans_hat + noise.alpha4*rot1_hat*rot1_hat + noise.alpha4*rot2_hat*rot2_hat);
+        let p3 = probability::log_prob_normal(base::normalize_angle(rot2 - rot2_hat), noise.alpha1*rot2_hat*rot2_hat + noise.alpha2*trans_hat*trans_hat);
+        p1 + p2 + p3
+    }
+
+
+    /// Density p(pos_end | odometry, pos_start) of the odometry motion model
+    /// See [OdometryModel::log_motion_model_odometry]
+    pub fn motion_model_odometry(&mut self, pos_start:base::Model2D<T>, pos_end:base::Model2D<T>, odometry_l:T, odometry_r:T, noise:&OdometryNoise<T>)->T{
+        self.log_motion_model_odometry(pos_start,pos_end,odometry_l,odometry_r,noise).exp()
     }
 
 }
 
-impl base::MotionUpdate2D for OdometryModel{
+
+/// Jacobian of the arc of length `distance` turning by `angle` with respect to the wheel increments
+/// (dl, dr) , chain rule through distance = (dl + dr)/2 and angle = (dr - dl)/L
+pub(crate) fn wheel_control_jacobian<T:base::Real>(integrator:base::Integrator, state:base::Model2D<T>, distance:T, angle:T, base_length:T)->base::ControlJacobianModel2D<T>{
+    let motion = integrator.motion_jacobian(state,distance,angle);
+    let half = base::real::<T>(0.5);
+    let mut data = base::ControlJacobianModel2D::zeros();
+    for (row,motion_row) in data.data.iter_mut().zip(motion.iter()){
+        row[0] = half*motion_row[0] - motion_row[1]/base_length;
+        row[1] = half*motion_row[0] + motion_row[1]/base_length;
+    }
+    data
+}
+
+
+impl<T:base::Real> base::MotionUpdate2D<T> for OdometryModel<T>{
     
-    fn update_coords_odometry(&mut self,odom_l:f32, odom_r:f32)->base::Model2D{ 
-        let params = match self.update_get_radius_angle_distance(odom_l,odom_r){
-            Ok(v)=>{
-                    self.update_position_coords(v)
-            },
-            Err(e)=>{
-                    self.update_position_coords_straight_line(e)
-            }
+    fn update_coords_odometry(&mut self,odom_l:T, odom_r:T)->base::Model2D<T>{ 
+        let increment = self.update_get_radius_angle_distance(odom_l,odom_r);
+        let params = if increment.is_straight(){
+            self.update_position_coords_straight_line(increment.change())
+        }else{
+            self.update_position_coords(increment.change())
         };
         self.update_odometry_readings(odom_l,odom_r);
         params
@@ -218,15 +358,16 @@ impl base::MotionUpdate2D for OdometryModel{
     /// The update_get_radius_angle_distance does update the current odometry value 
     /// It does not however do anything to the varibale that maybe probabilistic like the x , y and
     /// theta coordinates 
-    fn update_coords_odometry_stateless(&mut self,pos:base::Model2D,odom_l:f32, odom_r:f32)->base::Model2D{ 
-        let params = match self.update_get_radius_angle_distance(odom_l,odom_r){
-            Ok(v)=>{
-                    Self::update_position_coords_stateless(pos,v)
-            },
-            Err(e)=>{
-                    Self::update_position_coords_straight_line_stateless(pos,e)
-            }
+    /// The heading policy is applied , but the turns of `pos` are not counted
+    fn update_coords_odometry_stateless(&mut self,pos:base::Model2D<T>,odom_l:T, odom_r:T)->base::Model2D<T>{ 
+        let increment = self.update_get_radius_angle_distance(odom_l,odom_r);
+        let change = increment.change();
+        let mut params = if increment.is_straight(){
+            Self::update_position_coords_straight_line_stateless(pos,change)
+        }else{
+            self.integrator.integrate(pos,change.s,change.alpha)
         };
+        params.theta = self.heading.policy.apply(params.theta);
         self.update_odometry_readings(odom_l,odom_r);
         params
     }
@@ -235,16 +376,25 @@ impl base::MotionUpdate2D for OdometryModel{
     /// If working in an environment where you have to get the jacobian and the updated odometry 
     /// get the jacobian first. The functions `update_coords_odometry_{}_stateless` change the value
     /// of odometry of the Model internally which affects the jacobian values
-    fn get_jacobian_stateless(&mut self, pos:base::Model2D, odom_l:f32, odom_r:f32)->base::JacobianModel2D{
-        let params = match self.update_get_radius_angle_distance(odom_l,odom_r){
-            Ok(v)=>{
-                    Self::update_get_jacobian_stateless(pos,v)
-            },
-            Err(e)=>{
-                    Self::update_get_jacobian_straight_line_stateless(pos,e)
-            }
-        };
-        params
+    fn get_jacobian_stateless(&mut self, pos:base::Model2D<T>, odom_l:T, odom_r:T)->base::JacobianModel2D<T>{
+        let increment = self.update_get_radius_angle_distance(odom_l,odom_r);
+        let change = increment.change();
+        if increment.is_straight(){
+            Self::update_get_jacobian_straight_line_stateless(pos,change)
+        }else{
+            self.integrator.state_jacobian(pos,change.s,change.alpha)
+        }
+    }
+
+
+    /// Same as `get_jacobian_stateless` , the odometry readings of the model are not changed
+    fn get_control_jacobian_stateless(&mut self, pos:base::Model2D<T>, odom_l:T, odom_r:T)->base::ControlJacobianModel2D<T>{
+        let increment = self.update_get_radius_angle_distance(odom_l,odom_r);
+        if increment.is_straight(){
+            self.update_get_control_jacobian_straight_line_stateless(pos,increment.change())
+        }else{
+            self.update_get_control_jacobian_stateless(pos,increment.change())
+        }
     }
 }
 
@@ -263,11 +413,11 @@ mod tests {
         let wheel_l = 21.0;
         let wheel_r = 20.9;
         match newodommodel.update_get_radius_angle_distance(wheel_l,wheel_r){
-            Ok(v)=>{
+            super::MotionIncrement::Arc(v)=>{
                     newodommodel.update_position_coords(v);
             },
-            Err(e)=>{
-                    newodommodel.update_position_coords_straight_line(e);
+            e=>{
+                    newodommodel.update_position_coords_straight_line(e.change());
             }
         };
         println!("ODOM TEST");
@@ -280,12 +430,12 @@ mod tests {
         let wheel_l = 20.0;
         let wheel_r = wheel_l;
         match newodommodel.update_get_radius_angle_distance(wheel_l,wheel_r){
-            Ok(v)=>{
+            super::MotionIncrement::Arc(v)=>{
 
                     newodommodel.update_position_coords(v);
             },
-            Err(e)=>{
-                    newodommodel.update_position_coords_straight_line(e);
+            e=>{
+                    newodommodel.update_position_coords_straight_line(e.change());
             }
         };
 
@@ -361,11 +511,246 @@ mod tests {
 
 
 
+    #[test]
+    fn rot_trans_rot_test(){
+        let mut newodommodel:super::OdometryModel = super::OdometryModel::new(0.1);
+        let (rot1,trans,rot2) = newodommodel.get_rot_trans_rot(1.0,1.0);
+        assert_eq!((rot1,trans,rot2),(0.0,1.0,0.0));
+
+        let (rot1,trans,rot2) = newodommodel.get_rot_trans_rot(-1.0,-1.0);
+        assert!(rot1.abs()<1e-6 && rot2.abs()<1e-6);
+        assert!((trans+1.0).abs()<1e-6);
+
+        let (rot1,_trans,rot2) = newodommodel.get_rot_trans_rot(0.2,0.21);
+        assert!((rot1+rot2-0.1).abs()<1e-5);
+    }
+
+    #[test]
+    fn sample_odometry_model_test(){
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut newodommodel:super::OdometryModel = super::OdometryModel::new(0.1);
+        let initial_state = super::base::Model2D::new(0.,0.,0.5);
+
+        // without noise sampling is the same as the deterministic update
+        let noise = super::OdometryNoise::new(0.0,0.0,0.0,0.0);
+        let sample = newodommodel.sample_motion_model_odometry(initial_state,0.2,0.21,&noise,&mut rng);
+        let mut det_model = super::OdometryModel::new(0.1);
+        let expected = super::base::MotionUpdate2D::update_coords_odometry_stateless(&mut det_model,initial_state,0.2,0.21);
+        assert!((sample.x-expected.x).abs()<1e-5);
+        assert!((sample.y-expected.y).abs()<1e-5);
+        assert!((sample.theta-expected.theta).abs()<1e-5);
+
+        // with noise the samples scatter around the deterministic update
+        let noise = super::OdometryNoise::new(0.05,0.01,0.05,0.01);
+        let n = 2000;
+        let mut mean = (0.0,0.0,0.0);
+        for _ in 0..n{
+            let s = newodommodel.sample_motion_model_odometry(initial_state,0.2,0.21,&noise,&mut rng);
+            mean.0 += s.x/n as f32;
+            mean.1 += s.y/n as f32;
+            mean.2 += s.theta/n as f32;
+        }
+        assert!((mean.0-expected.x).abs()<1e-2);
+        assert!((mean.1-expected.y).abs()<1e-2);
+        assert!((mean.2-expected.theta).abs()<1e-2);
+    }
+
+    #[test]
+    fn heading_policy_odometry_model_test(){
+        use super::base::{HeadingPolicy,MotionUpdate2D};
+        // turning in place by one radian per update , 20 radians are three full turns
+        for policy in [HeadingPolicy::WrapSigned,HeadingPolicy::WrapPositive,HeadingPolicy::Continuous].iter(){
+            let mut model = super::OdometryModel::with_heading_policy(0.1f64,*policy);
+            for i in 1..=20{
+                let pos = model.update_coords_odometry(-0.05*i as f64,0.05*i as f64);
+                match policy{
+                    HeadingPolicy::WrapSigned=>assert!(pos.theta >= -std::f64::consts::PI && pos.theta < std::f64::consts::PI),
+                    HeadingPolicy::WrapPositive=>assert!(pos.theta >= 0.0 && pos.theta < 2.0*std::f64::consts::PI),
+                    HeadingPolicy::Continuous=>assert!((pos.theta-i as f64).abs()<1e-9)
+                }
+                assert!((super::base::normalize_angle(pos.theta-i as f64)).abs()<1e-9);
+            }
+            assert!((model.unwrapped_heading()-20.0).abs()<1e-9);
+            assert_eq!(model.turns(),3);
+            assert_eq!(model.heading_policy(),*policy);
+        }
+
+        // the stateless update wraps too , turning backwards counts negative turns
+        let mut model = super::OdometryModel::with_heading_policy(0.1f64,HeadingPolicy::WrapPositive);
+        let pos = model.update_coords_odometry_stateless(super::base::Model2D::new(0.0,0.0,0.5),0.05,-0.05);
+        assert!((pos.theta-(2.0*std::f64::consts::PI-0.5)).abs()<1e-9);
+        model.update_coords_odometry(0.4,-0.4);
+        assert!((model.unwrapped_heading()+7.0).abs()<1e-9);
+        assert_eq!(model.turns(),-1);
+    }
+
+    #[test]
+    fn motion_increment_test(){
+        use super::base::MotionUpdate2D;
+        use super::MotionIncrement;
+        let mut model = super::OdometryModel::new(0.1f64);
+        model.update_odometry_readings(1.0,1.0);
+        assert_eq!(model.update_get_radius_angle_distance(1.0,1.0),MotionIncrement::Stationary);
+        assert_eq!(model.update_get_radius_angle_distance(1.5,1.5),MotionIncrement::Straight(super::ChangeParams::new(0.0,0.0,0.5)));
+        match model.update_get_radius_angle_distance(1.1,1.3){
+            MotionIncrement::Arc(change)=>assert!((change.R-0.1).abs()<1e-12 && (change.alpha-2.0).abs()<1e-12 && (change.s-0.2).abs()<1e-12),
+            other=>panic!("{:?}",other)
+        }
+
+        // opposite wheel increments turn in place
+        let increment = model.update_get_radius_angle_distance(0.75,1.25);
+        assert!(matches!(increment,MotionIncrement::RotateInPlace(_)));
+        assert!((increment.change().alpha-5.0).abs()<1e-12 && increment.change().s==0.0 && !increment.is_straight());
+        let start = super::base::Model2D::new(0.3,0.4,0.5);
+        let pos = model.update_coords_odometry_stateless(start,0.75,1.25);
+        assert!(pos.x==start.x && pos.y==start.y && (pos.theta-5.5).abs()<1e-12);
+
+        // single precision increments that differ by one unit in the last place are not a turn
+        let mut model = super::OdometryModel::new(0.1f32);
+        let ulp = 0.5f32*f32::EPSILON;
+        let increment = model.update_get_radius_angle_distance(0.5,0.5+ulp);
+        assert!(matches!(increment,MotionIncrement::Straight(_)) && increment.change().alpha==0.0);
+        let increment = model.update_get_radius_angle_distance(-0.5,0.5+ulp);
+        assert!(matches!(increment,MotionIncrement::RotateInPlace(_)) && increment.change().s==0.0);
+        assert!(matches!(model.update_get_radius_angle_distance(0.5,0.5+1e-3),MotionIncrement::Arc(_)));
+
+        // a steering angle far below the precision of the distance drives straight
+        let model = crate::ackermann_motion_model::AckermannModel::new(0.5f32);
+        assert!(model.update_get_radius_angle_distance(0.5,1e-9).is_straight());
+        assert!(!model.update_get_radius_angle_distance(0.5,1e-3).is_straight());
+    }
+
+    #[test]
+    fn small_angle_odometry_model_test(){
+        // the radius of these arcs is far beyond the precision of f32 , the position must not suffer
+        let state:super::base::Model2D = super::base::Model2D::new(1.0,2.0,0.7);
+        let model = super::OdometryModel::new(0.1f32);
+        let s = 0.5f32;
+        for alpha in [1e-3f32,1e-5,1e-7,-1e-7,1e-9].iter(){
+            let arc = || super::ChangeParams::new(s/alpha,*alpha,s);
+            let pos = super::OdometryModel::update_position_coords_stateless(state,arc());
+
+            // taylor series of the arc in double precision
+            let (s64,a64,t64) = (s as f64,*alpha as f64,state.theta as f64);
+            let (forward,left) = (s64*(1.0 - a64*a64/6.0), s64*a64/2.0);
+            let x = state.x as f64 + forward*t64.cos() - left*t64.sin();
+            let y = state.y as f64 + forward*t64.sin() + left*t64.cos();
+            assert!((pos.x as f64-x).abs()<1e-6 && (pos.y as f64-y).abs()<1e-6,"{} : {:?}",alpha,pos);
+
+            // the jacobians approach the straight line ones
+            let straight = || super::ChangeParams::new(0.0,0.0,s);
+            let g = super::OdometryModel::update_get_jacobian_stateless(state,arc());
+            let g_straight = super::OdometryModel::update_get_jacobian_straight_line_stateless(state,straight());
+            let v = model.update_get_control_jacobian_stateless(state,arc());
+            let v_straight = model.update_get_control_jacobian_straight_line_stateless(state,straight());
+            let bound = 1e-5 + 2.0*alpha.abs();
+            for row in 0..3{
+                assert!((g.data[row][2]-g_straight.data[row][2]).abs()<bound);
+                for col in 0..2{
+                    assert!((v.data[row][col]-v_straight.data[row][col]).abs()<bound*10.0,"{} ({},{})",alpha,row,col);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn integrators_odometry_model_test(){
+        use super::base::{Integrator,MotionUpdate2D};
+        let state = super::base::Model2D::new(0.3f64,-0.2,0.7);
+        // an arc of length 0.21 turning by 0.4
+        let (dl,dr) = (0.19,0.23);
+        let mut model = super::OdometryModel::new(0.1f64);
+        let exact = model.update_coords_odometry_stateless(state,dl,dr);
+        for integrator in [Integrator::ExactArc,Integrator::Midpoint,Integrator::Euler].iter(){
+            let mut model = super::OdometryModel::new(0.1f64);
+            model.set_integrator(*integrator);
+            let pos = model.update_coords_odometry_stateless(state,dl,dr);
+            let course = match integrator{
+                Integrator::ExactArc=>0.7+0.2,
+                Integrator::Midpoint=>0.7+0.2,
+                Integrator::Euler=>0.7
+            };
+            // the exact chord is shorter than the arc
+            let length = if *integrator==Integrator::ExactArc { 0.21*0.2f64.sin()/0.2 } else { 0.21 };
+            assert!((pos.x-(state.x+length*f64::cos(course))).abs()<1e-12 && (pos.y-(state.y+length*f64::sin(course))).abs()<1e-12);
+            assert!((pos.theta-exact.theta).abs()<1e-12);
+
+            // jacobians against central differences
+            model.update_odometry_readings(0.0,0.0);
+            let g = model.get_jacobian_stateless(state,dl,dr);
+            let v = model.get_control_jacobian_stateless(state,dl,dr);
+            let h = 1e-6;
+            let mut at = |pos:super::base::Model2D<f64>,l:f64,r:f64|{
+                let pos = model.update_coords_odometry_stateless(pos,l,r);
+                model.update_odometry_readings(0.0,0.0);
+                pos
+            };
+            crate::tests::assert_numeric_jacobian(&g.data,|p:[f64;3]| at(super::base::Model2D::new(p[0],p[1],p[2]),dl,dr),[state.x,state.y,state.theta],h,1e-8);
+            crate::tests::assert_numeric_jacobian(&v.data,|u:[f64;2]| at(state,u[0],u[1]),[dl,dr],h,1e-7);
+        }
+    }
+
+    #[test]
+    fn control_jacobian_odometry_model_test(){
+        use super::base::MotionUpdate2D;
+        // compare against central differences of the update
+        let state = super::base::Model2D::new(0.3,-0.2,0.7);
+        for (dl,dr) in [(0.2f32,0.23f32),(0.1,0.1)].iter(){
+            let mut model = super::OdometryModel::new(0.1);
+            let jacobian = model.get_control_jacobian_stateless(state,*dl,*dr);
+            let h = 1e-3;
+            for (col,(el,er)) in [(h,0.0),(0.0,h)].iter().enumerate(){
+                let mut model = super::OdometryModel::new(0.1);
+                let plus = model.update_coords_odometry_stateless(state,dl+el,dr+er);
+                let mut model = super::OdometryModel::new(0.1);
+                let minus = model.update_coords_odometry_stateless(state,dl-el,dr-er);
+                let numeric = [(plus.x-minus.x)/(2.0*h),(plus.y-minus.y)/(2.0*h),(plus.theta-minus.theta)/(2.0*h)];
+                for (row,value) in numeric.iter().enumerate(){
+                    assert!((jacobian.data[row][col]-value).abs()<2e-2,"dl {} row {} col {} : {} vs {}",dl,row,col,jacobian.data[row][col],value);
+                }
+            }
+        }
+    }
+
+
+    #[test]
+    fn radius_odometry_model_test(){
+        use super::base::MotionUpdate2D;
+        // the radius is the one of the axle center , s/alpha , not the one of the left wheel
+        let mut model = super::OdometryModel::new(0.1f64);
+        let change = model.update_get_radius_angle_distance(0.1,0.3).change();
+        assert!((change.R-0.1).abs()<1e-12 && (change.R-change.s/change.alpha).abs()<1e-12);
+
+        // the axle center stays on the circle of radius R around the center of rotation
+        let pos = model.update_coords_odometry_stateless(super::base::Model2D::new(0.0,0.0,0.0),0.1,0.3);
+        assert!((pos.x.hypot(pos.y-0.1)-0.1).abs()<1e-12 && (pos.theta-2.0).abs()<1e-12);
+    }
+
+    #[test]
+    fn density_odometry_model_test(){
+        use super::base::MotionUpdate2D;
+        let mut newodommodel:super::OdometryModel = super::OdometryModel::new(0.1);
+        let initial_state = super::base::Model2D::new(1.0,2.0,0.5);
+        let noise = super::OdometryNoise::new(0.05,0.01,0.05,0.01);
+
+        let mut det_model = super::OdometryModel::new(0.1);
+        let expected = det_model.update_coords_odometry_stateless(initial_state,0.2,0.21);
+        let off = super::base::Model2D::new(expected.x+0.01,expected.y-0.01,expected.theta+0.02);
+
+        let p_expected = newodommodel.motion_model_odometry(initial_state,expected,0.2,0.21,&noise);
+        let p_off = newodommodel.motion_model_odometry(initial_state,off,0.2,0.21,&noise);
+        assert!(p_expected>p_off);
+        let log_p = newodommodel.log_motion_model_odometry(initial_state,expected,0.2,0.21,&noise);
+        assert!((log_p.exp()-p_expected).abs()<1e-3*p_expected);
+    }
+
+
     #[test]
     fn files_odometry_model_test(){
         use super::base::MotionUpdate2D;
         let mut initial_state = super::base::Model2D::new(0.,0.,0.);
-        let mut newodommodel = super::OdometryModel::new(0.1);
+        let mut newodommodel:super::OdometryModel = super::OdometryModel::new(0.1);
         let wheel_l = 21.0;
         let wheel_r  =20.9;
         initial_state = newodommodel.update_coords_odometry_stateless(initial_state,wheel_l,wheel_r);
@@ -0,0 +1,241 @@
+//! Monte carlo localization (Probabilistic Robotics, Thrun et al. , table 8.2)
+//! The prediction step samples every particle from
+//! [OdometryModel::sample_motion_model_odometry], the measurement likelihood is pluggable.
+use crate::base;
+use crate::ekf::MeasurementModel2D;
+use crate::odometry_motion_model::{OdometryModel,OdometryNoise};
+use rand::Rng;
+
+
+/// A pose hypothesis and its importance weight
+#[derive(Copy,Clone,Debug)]
+pub struct Particle<T:base::Real=f32>{
+    pub pose:base::Model2D<T>,
+    pub weight:T
+}
+impl<T:base::Real> Particle<T>{
+    pub fn new(pose:base::Model2D<T>,weight:T)->Particle<T>{
+        Particle{
+            pose,
+            weight
+        }
+    }
+}
+
+
+/// Resampling algorithms
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum ResamplingScheme{
+    /// Low variance sampler (systematic resampling , table 4.4) , one random number for all
+    /// particles
+    LowVariance,
+    /// One random number in each of the M strata of [0, 1)
+    Stratified,
+    /// M independent draws from the weights
+    Multinomial
+}
+
+
+/// Likelihood p(z | x) of a measurement z at a pose, it does not have to be normalized
+pub trait MeasurementLikelihood<Z,T:base::Real=f32>{
+    fn likelihood(&self, pos:base::Model2D<T>, measurement:&Z)->T;
+}
+
+/// Every measurement model of the kalman filters is a gaussian likelihood.
+/// The normalization constant does not depend on the pose , so it is left out
+impl<const M:usize,T:base::Real,U:MeasurementModel2D<M,T>> MeasurementLikelihood<[T;M],T> for U{
+    fn likelihood(&self, pos:base::Model2D<T>, measurement:&[T;M])->T{
+        let residual = self.residual(measurement,&self.predict_measurement(pos));
+        let noise_inv = match crate::ekf::invert(self.get_noise()){
+            Some(v)=>v,
+            None=>return T::zero()
+        };
+        let mut mahalanobis = T::zero();
+        for (i,row) in noise_inv.iter().enumerate(){
+            for (j,&value) in row.iter().enumerate(){
+                mahalanobis += residual[i]*value*residual[j];
+            }
+        }
+        (-mahalanobis/base::real(2.0)).exp()
+    }
+}
+
+
+
+/// A set of weighted particles
+pub struct ParticleFilter<T:base::Real=f32>{
+    pub particles:Vec<Particle<T>>
+}
+
+impl<T:base::Real> ParticleFilter<T>{
+    /// The weights are normalized
+    pub fn new(particles:Vec<Particle<T>>)->ParticleFilter<T>{
+        let mut filter = ParticleFilter{
+            particles
+        };
+        filter.normalize_weights();
+        filter
+    }
+
+    /// `count` particles drawn around `mean` with the given standard deviations
+    pub fn from_gaussian<R:Rng+?Sized>(mean:base::Model2D<T>, std_x:T, std_y:T, std_theta:T, count:usize, rng:&mut R)->ParticleFilter<T>{
+        let weight = T::one()/base::real(count as f64);
+        let particles = (0..count).map(|_|{
+            let pose = base::Model2D::new(
+                mean.x + crate::probability::sample_normal(std_x*std_x,rng),
+                mean.y + crate::probability::sample_normal(std_y*std_y,rng),
+                mean.theta + crate::probability::sample_normal(std_theta*std_theta,rng));
+            Particle::new(pose,weight)
+        }).collect();
+        ParticleFilter{
+            particles
+        }
+    }
+
+
+    /// Samples every particle from the odometry motion model and then updates the odometry
+    /// readings of the model
+    pub fn predict<R:Rng+?Sized>(&mut self, model:&mut OdometryModel<T>, odom_l:T, odom_r:T, noise:&OdometryNoise<T>, rng:&mut R){
+        for particle in self.particles.iter_mut(){
+            particle.pose = model.sample_motion_model_odometry(particle.pose,odom_l,odom_r,noise,rng);
+        }
+        model.update_odometry_readings(odom_l,odom_r);
+    }
+
+
+    /// Multiplies the weights by the likelihood of the measurement and normalizes them.
+    /// If no particle explains the measurement the weights are reset to uniform
+    pub fn update<Z,L:MeasurementLikelihood<Z,T>>(&mut self, likelihood:&L, measurement:&Z){
+        for particle in self.particles.iter_mut(){
+            particle.weight *= likelihood.likelihood(particle.pose,measurement);
+        }
+        self.normalize_weights();
+    }
+
+
+    /// Scales the weights to sum to one , or to uniform weights if they sum to zero
+    pub fn normalize_weights(&mut self){
+        let total:T = self.particles.iter().map(|p| p.weight).sum();
+        let count = base::real::<T>(self.particles.len() as f64);
+        if total > T::zero() && total.is_finite(){
+            self.particles.iter_mut().for_each(|p| p.weight /= total);
+        }else{
+            self.particles.iter_mut().for_each(|p| p.weight = T::one()/count);
+        }
+    }
+
+
+    /// 1/sum(w^2) of the normalized weights , a common criterion for when to resample
+    pub fn effective_sample_size(&self)->T{
+        let sum_squares:T = self.particles.iter().map(|p| p.weight*p.weight).sum();
+        if sum_squares > T::zero() { T::one()/sum_squares } else { T::zero() }
+    }
+
+
+    /// Draws a new set of the same size with uniform weights
+    pub fn resample<R:Rng+?Sized>(&mut self, scheme:ResamplingScheme, rng:&mut R){
+        let count = self.particles.len();
+        if count==0{
+            return
+        }
+        let step = T::one()/base::real(count as f64);
+        let mut uniform = || base::real::<T>(rng.gen::<f64>());
+        let positions:Vec<T> = match scheme{
+            ResamplingScheme::LowVariance=>{
+                let r = uniform()*step;
+                (0..count).map(|m| r + base::real::<T>(m as f64)*step).collect()
+            },
+            ResamplingScheme::Stratified=>{
+                (0..count).map(|m| (base::real::<T>(m as f64) + uniform())*step).collect()
+            },
+            ResamplingScheme::Multinomial=>{
+                let mut draws:Vec<T> = (0..count).map(|_| uniform()).collect();
+                draws.sort_by(|a,b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+                draws
+            }
+        };
+
+        // walk the sorted positions along the cumulative weights
+        let mut resampled = Vec::with_capacity(count);
+        let mut index = 0;
+        let mut cumulative = self.particles[0].weight;
+        for u in positions{
+            while u > cumulative && index < count-1{
+                index += 1;
+                cumulative += self.particles[index].weight;
+            }
+            resampled.push(Particle::new(self.particles[index].pose,step));
+        }
+        self.particles = resampled;
+    }
+
+
+    /// Weighted mean of the particles , theta is the circular mean
+    pub fn mean(&self)->base::Model2D<T>{
+        let mut mean = base::Model2D::new(T::zero(),T::zero(),T::zero());
+        let (mut sin_sum,mut cos_sum) = (T::zero(),T::zero());
+        for p in self.particles.iter(){
+            mean.x += p.weight*p.pose.x;
+            mean.y += p.weight*p.pose.y;
+            sin_sum += p.weight*p.pose.theta.sin();
+            cos_sum += p.weight*p.pose.theta.cos();
+        }
+        mean.theta = sin_sum.atan2(cos_sum);
+        mean
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    #[test]
+    fn resample_test(){
+        let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+        for scheme in [super::ResamplingScheme::LowVariance,super::ResamplingScheme::Stratified,super::ResamplingScheme::Multinomial].iter(){
+            let particles = [
+                super::Particle::new(crate::base::Model2D::new(0.0,0.0,0.0),0.0),
+                super::Particle::new(crate::base::Model2D::new(1.0,0.0,0.0),0.75),
+                super::Particle::new(crate::base::Model2D::new(2.0,0.0,0.0),0.25),
+                super::Particle::new(crate::base::Model2D::new(3.0,0.0,0.0),0.0),
+            ];
+            let mut filter:super::ParticleFilter = super::ParticleFilter::new(particles.repeat(25));
+            filter.resample(*scheme,&mut rng);
+            assert_eq!(filter.particles.len(),100);
+            let ones = filter.particles.iter().filter(|p| p.pose.x==1.0).count();
+            let twos = filter.particles.iter().filter(|p| p.pose.x==2.0).count();
+            assert_eq!(ones+twos,100);
+            if *scheme==super::ResamplingScheme::LowVariance{
+                assert_eq!(ones,75);
+            }
+            assert!((filter.effective_sample_size()-100.0).abs()<1e-2);
+        }
+    }
+
+    #[test]
+    fn localization_test(){
+        let mut rng = rand::rngs::StdRng::seed_from_u64(11);
+        let mut model = crate::odometry_motion_model::OdometryModel::new(0.1);
+        let mut truth_model = crate::odometry_motion_model::OdometryModel::new(0.1);
+        let noise = crate::odometry_motion_model::OdometryNoise::new(0.05,0.01,0.05,0.01);
+        let sensor = crate::ekf::PositionMeasurement::new(0.01,0.01);
+
+        let mut truth = crate::base::Model2D::new(0.0,0.0,0.0);
+        let mut filter = super::ParticleFilter::from_gaussian(truth,0.2,0.2,0.1,500,&mut rng);
+        for step in 1..=20{
+            let (l,r) = (0.05*step as f32,0.055*step as f32);
+            truth = crate::base::MotionUpdate2D::update_coords_odometry_stateless(&mut truth_model,truth,l,r);
+            filter.predict(&mut model,l,r,&noise,&mut rng);
+            filter.update(&sensor,&[truth.x,truth.y]);
+            if filter.effective_sample_size() < 250.0{
+                filter.resample(super::ResamplingScheme::LowVariance,&mut rng);
+            }
+        }
+        let mean = filter.mean();
+        assert!((mean.x-truth.x).abs()<0.05);
+        assert!((mean.y-truth.y).abs()<0.05);
+    }
+}
@@ -0,0 +1,49 @@
+//! Helpers for the probabilistic motion models : sampling from and evaluating normal
+//! distributions given their variance (as written in Probabilistic Robotics, Thrun et al.)
+use crate::base;
+use rand::Rng;
+use rand_distr::{Distribution,StandardNormal};
+
+
+/// Draws a sample from a zero mean normal distribution with the given variance
+/// A variance of zero (or less) always returns zero
+pub fn sample_normal<T:base::Real,R:Rng+?Sized>(variance:T, rng:&mut R)->T{
+    if variance <= T::zero() {
+        return T::zero()
+    }
+    let sample:f64 = StandardNormal.sample(rng);
+    base::real::<T>(sample)*variance.sqrt()
+}
+
+
+/// Log of the density of a zero mean normal distribution with the given variance at `a`
+/// If the variance is zero the distribution is a dirac delta, this returns 0 at `a==0` and
+/// negative infinity everywhere else
+pub fn log_prob_normal<T:base::Real>(a:T, variance:T)->T{
+    if variance <= T::zero() {
+        return if a==T::zero() { T::zero() } else { T::neg_infinity() }
+    }
+    let two = base::real::<T>(2.0);
+    -(two*T::PI()*variance).ln()/two - a*a/(two*variance)
+}
+
+
+/// Density of a zero mean normal distribution with the given variance at `a`
+/// See [log_prob_normal] for the zero variance case
+pub fn prob_normal<T:base::Real>(a:T, variance:T)->T{
+    log_prob_normal(a,variance).exp()
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn prob_normal_test(){
+        let p = super::prob_normal(0.0f32,1.0);
+        assert!((p-0.398_942_3).abs()<1e-6);
+        let p = super::prob_normal(2.0f32,4.0);
+        assert!((p-0.120_985_36).abs()<1e-6);
+        assert_eq!(super::log_prob_normal(0.1f32,0.0),f32::NEG_INFINITY);
+    }
+}
@@ -0,0 +1,183 @@
+use crate::base;
+
+
+/// A skid steered or tracked vehicle described by its instantaneous centers of rotation (ICR)
+/// (Martinez et al. , "Approximating kinematics for tracked mobile robots").
+/// `icr_left` and `icr_right` are the lateral positions (left positive) of the ICRs of the left
+/// and right track , `icr_x` the longitudinal position of the ICR of the vehicle (forward
+/// positive). An ideal differential drive has icr_left = L/2 , icr_right = -L/2 , icr_x = 0 ,
+/// slipping tracks move the track ICRs outwards so the effective base length icr_left - icr_right
+/// grows. odometry_l and odometry_r are the cumulative DISTANCES covered by the tracks , like
+/// [crate::odometry_motion_model::OdometryModel].
+#[derive(Clone)]
+pub struct SkidSteerModel<T:base::Real=f32>{
+    odometry_l:T,
+    odometry_r:T,
+    pub x_t:base::Model2D<T>,
+    pub icr_left:T,
+    pub icr_right:T,
+    pub icr_x:T
+}
+
+impl<T:base::Real> SkidSteerModel<T>{
+
+    pub fn new(icr_left:T, icr_right:T, icr_x:T)->SkidSteerModel<T>{
+        SkidSteerModel{
+            odometry_l:T::zero(),
+            odometry_r:T::zero(),
+            x_t:base::Model2D::new(T::zero(),T::zero(),T::zero()),
+            icr_left,
+            icr_right,
+            icr_x
+        }
+    }
+
+    /// The ICRs of a differential drive without slip
+    pub fn from_base_length(base_length:T)->SkidSteerModel<T>{
+        let half = base_length/base::real(2.0);
+        Self::new(half,-half,T::zero())
+    }
+
+
+    /// Distance between the track ICRs , the base length a differential drive model would need
+    pub fn effective_base_length(&self)->T{
+        self.icr_left - self.icr_right
+    }
+
+
+    /// Body displacement (forward, left, rotation) for the track increments (dl, dr).
+    /// Track speeds give the body twist (vx, vy, omega) the same way
+    pub fn body_motion(&self, diff_l:T, diff_r:T)->(T,T,T){
+        let base_length = self.effective_base_length();
+        let rotation = (diff_r - diff_l)/base_length;
+        (
+            (diff_r*self.icr_left - diff_l*self.icr_right)/base_length,
+            -self.icr_x*rotation,
+            rotation
+        )
+    }
+
+
+    /// Jacobian of [SkidSteerModel::body_motion] , one column (forward, left, rotation) for dl and
+    /// one for dr
+    fn body_motion_jacobian(&self)->[[T;3];2]{
+        let base_length = self.effective_base_length();
+        [
+            [-self.icr_right/base_length, self.icr_x/base_length, -T::one()/base_length],
+            [self.icr_left/base_length, -self.icr_x/base_length, T::one()/base_length]
+        ]
+    }
+
+
+    pub fn update_odometry_readings(&mut self, odometry_l:T, odometry_r:T){
+        self.odometry_l = odometry_l;
+        self.odometry_r = odometry_r;
+    }
+
+
+    /// New coordinates after the tracks covered the distances (dl, dr) , the stored readings are
+    /// not used
+    pub fn update_coords_stateless(&self, state:base::Model2D<T>, diff_l:T, diff_r:T)->base::Model2D<T>{
+        let (forward,left,rotation) = self.body_motion(diff_l,diff_r);
+        base::integrate_body_motion(state,forward,left,rotation)
+    }
+
+
+    /// Gets the jacobian of the new coordinates with respect to (x, y, theta)
+    pub fn update_get_jacobian_stateless(&self, state:base::Model2D<T>, diff_l:T, diff_r:T)->base::JacobianModel2D<T>{
+        let new = self.update_coords_stateless(state,diff_l,diff_r);
+        let mut data = base::JacobianModel2D::identity();
+        let _ = data.column(2,(state.y - new.y, new.x - state.x, T::one()));
+        data
+    }
+
+
+    /// Gets the jacobian of the new coordinates with respect to the track increments (dl, dr)
+    pub fn update_get_control_jacobian_stateless(&self, state:base::Model2D<T>, diff_l:T, diff_r:T)->base::ControlJacobianModel2D<T>{
+        let (forward,left,rotation) = self.body_motion(diff_l,diff_r);
+        let motion = base::integrate_body_motion_jacobian(state,forward,left,rotation);
+        let body = self.body_motion_jacobian();
+
+        let mut data = base::ControlJacobianModel2D::zeros();
+        for (row,motion_row) in data.data.iter_mut().zip(motion.iter()){
+            for (value,column) in row.iter_mut().zip(body.iter()){
+                *value = motion_row.iter().zip(column.iter()).fold(T::zero(),|sum,(a,b)| sum + *a**b);
+            }
+        }
+        data
+    }
+}
+
+
+/// Like [crate::odometry_motion_model::OdometryModel] the readings are cumulative track distances
+impl<T:base::Real> base::MotionUpdate2D<T> for SkidSteerModel<T>{
+
+    fn update_coords_odometry(&mut self, odom_l:T, odom_r:T)->base::Model2D<T>{
+        self.x_t = base::MotionUpdate2D::update_coords_odometry_stateless(self,self.x_t,odom_l,odom_r);
+        self.x_t
+    }
+
+    fn update_coords_odometry_stateless(&mut self, pos:base::Model2D<T>, odom_l:T, odom_r:T)->base::Model2D<T>{
+        let pos = self.update_coords_stateless(pos,odom_l - self.odometry_l,odom_r - self.odometry_r);
+        self.update_odometry_readings(odom_l,odom_r);
+        pos
+    }
+
+    fn get_jacobian_stateless(&mut self, pos:base::Model2D<T>, odom_l:T, odom_r:T)->base::JacobianModel2D<T>{
+        self.update_get_jacobian_stateless(pos,odom_l - self.odometry_l,odom_r - self.odometry_r)
+    }
+
+    fn get_control_jacobian_stateless(&mut self, pos:base::Model2D<T>, odom_l:T, odom_r:T)->base::ControlJacobianModel2D<T>{
+        self.update_get_control_jacobian_stateless(pos,odom_l - self.odometry_l,odom_r - self.odometry_r)
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::base::MotionUpdate2D;
+
+    #[test]
+    fn ideal_skid_steer_test(){
+        // without slip the model is the differential drive
+        let mut skid = super::SkidSteerModel::from_base_length(0.1054f64);
+        let mut odometry = crate::odometry_motion_model::OdometryModel::new(0.1054f64);
+        let start = crate::base::Model2D::new(0.2,0.1,0.5);
+        let (mut a,mut b) = (start,start);
+        for (l,r) in [(0.1,0.12),(0.2,0.2),(0.25,0.31),(0.3,0.28)].iter(){
+            a = skid.update_coords_odometry_stateless(a,*l,*r);
+            b = odometry.update_coords_odometry_stateless(b,*l,*r);
+        }
+        assert!((a.x-b.x).abs()<1e-12 && (a.y-b.y).abs()<1e-12 && (a.theta-b.theta).abs()<1e-12);
+    }
+
+    #[test]
+    fn slipping_skid_steer_test(){
+        // the tracks slip , the vehicle turns less than a differential drive of the track spacing
+        let model = super::SkidSteerModel::new(0.3f64,-0.35,0.05);
+        assert!((model.effective_base_length()-0.65).abs()<1e-12);
+        // turning in place moves the center sideways when the vehicle ICR is not at the center
+        let (forward,left,rotation) = model.body_motion(-0.65,0.65);
+        assert!((rotation-2.0).abs()<1e-12);
+        assert!((forward-(0.65*0.3-0.65*0.35)/0.65).abs()<1e-12);
+        assert!((left+0.1).abs()<1e-12);
+    }
+
+    #[test]
+    fn jacobians_skid_steer_test(){
+        let model = super::SkidSteerModel::new(0.3f64,-0.35,0.05);
+        let state = crate::base::Model2D::new(0.4,-0.3,1.1);
+        let h = 1e-6;
+        for (dl,dr) in [(0.1,0.3),(0.2,0.2),(-0.4,0.4),(0.2,0.2+1e-9)].iter(){
+            let g = model.update_get_jacobian_stateless(state,*dl,*dr);
+            let update = |p:[f64;3]| model.update_coords_stateless(crate::base::Model2D::new(p[0],p[1],p[2]),*dl,*dr);
+            crate::tests::assert_numeric_jacobian(&g.data,update,[state.x,state.y,state.theta],h,1e-8);
+
+            let v = model.update_get_control_jacobian_stateless(state,*dl,*dr);
+            let update = |u:[f64;2]| model.update_coords_stateless(state,u[0],u[1]);
+            crate::tests::assert_numeric_jacobian(&v.data,update,[*dl,*dr],h,1e-7);
+        }
+    }
+}
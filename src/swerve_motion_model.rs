@@ -0,0 +1,200 @@
+use crate::base;
+
+
+/// A holonomic base with N independently steered and driven modules (swerve drive).
+/// `modules` are the positions (forward, left) of the modules in the robot frame. Each module reads
+/// its steering angle , relative to the heading of the robot and positive to the left , and the
+/// DISTANCE covered by its wheel. Like [crate::odometry_motion_model::OdometryModel] the distances
+/// are cumulative.
+/// The module velocities of a rigid body are rarely consistent with each other , the body twist is
+/// the least squares fit to all of them
+#[derive(Clone)]
+pub struct SwerveModel<T:base::Real=f32>{
+    odometry:Vec<T>,
+    pub x_t:base::Model2D<T>,
+    modules:Vec<(T,T)>,
+    /// Inverse of A^T*A , A mapping the body twist to the module velocities
+    normal_inverse:[[T;3];3]
+}
+
+impl<T:base::Real> SwerveModel<T>{
+
+    /// Returns None if the modules do not determine the rotation , fewer than two distinct positions
+    pub fn new(modules:Vec<(T,T)>)->Option<SwerveModel<T>>{
+        if modules.len()<2{
+            return None
+        }
+        let mut normal = [[T::zero();3];3];
+        for (x,y) in modules.iter(){
+            normal[0][0] += T::one();
+            normal[1][1] += T::one();
+            normal[0][2] -= *y;
+            normal[1][2] += *x;
+            normal[2][2] += *x**x + *y**y;
+        }
+        normal[2][0] = normal[0][2];
+        normal[2][1] = normal[1][2];
+        let normal_inverse = crate::ekf::invert(normal)?;
+        Some(SwerveModel{
+            odometry:vec![T::zero();modules.len()],
+            x_t:base::Model2D::new(T::zero(),T::zero(),T::zero()),
+            modules,
+            normal_inverse
+        })
+    }
+
+    /// Four modules at the corners of a rectangle , ordered front left , front right , rear left ,
+    /// rear right like [crate::mecanum_motion_model::MecanumModel]
+    pub fn rectangular(half_length:T, half_width:T)->Option<SwerveModel<T>>{
+        Self::new(vec![(half_length,half_width),(half_length,-half_width),(-half_length,half_width),(-half_length,-half_width)])
+    }
+
+
+    pub fn modules(&self)->&[(T,T)]{
+        &self.modules
+    }
+
+
+    /// Forward kinematics , the least squares body twist (vx, vy, omega) in the robot frame for
+    /// the module states (steering angle, wheel speed). Module states with distances instead of
+    /// speeds give the body displacement (forward, left, rotation) the same way.
+    /// Returns None if the number of states differs from the number of modules
+    pub fn body_twist(&self, states:&[(T,T)])->Option<(T,T,T)>{
+        if states.len()!=self.modules.len(){
+            return None
+        }
+        // A^T * module velocities
+        let mut projected = [T::zero();3];
+        for ((x,y),(angle,speed)) in self.modules.iter().zip(states.iter()){
+            let (sin_a,cos_a) = angle.sin_cos();
+            let (vx,vy) = (*speed*cos_a,*speed*sin_a);
+            projected[0] += vx;
+            projected[1] += vy;
+            projected[2] += *x*vy - *y*vx;
+        }
+        let mut twist = [T::zero();3];
+        for (value,row) in twist.iter_mut().zip(self.normal_inverse.iter()){
+            *value = row.iter().zip(projected.iter()).fold(T::zero(),|sum,(a,b)| sum + *a**b);
+        }
+        Some((twist[0],twist[1],twist[2]))
+    }
+
+
+    /// Inverse kinematics , the module states (steering angle, wheel speed) for the body twist
+    /// (vx, vy, omega). The angles are in (-pi, pi] and the speeds not negative , a module that
+    /// does not move gets the angle zero
+    pub fn module_states(&self, vx:T, vy:T, omega:T)->Vec<(T,T)>{
+        self.modules.iter().map(|(x,y)|{
+            let (module_vx,module_vy) = (vx - omega**y, vy + omega**x);
+            (module_vy.atan2(module_vx),module_vx.hypot(module_vy))
+        }).collect()
+    }
+
+
+    pub fn update_odometry_readings(&mut self, states:&[(T,T)]){
+        for (stored,(_,distance)) in self.odometry.iter_mut().zip(states.iter()){
+            *stored = *distance;
+        }
+    }
+
+
+    /// New coordinates after the modules covered the distances of `increments` (steering angle,
+    /// distance) , None if the number of increments differs from the number of modules
+    pub fn update_coords_stateless(&self, state:base::Model2D<T>, increments:&[(T,T)])->Option<base::Model2D<T>>{
+        let (forward,left,rotation) = self.body_twist(increments)?;
+        Some(base::integrate_body_motion(state,forward,left,rotation))
+    }
+
+
+    /// Gets the jacobian of the new coordinates with respect to (x, y, theta)
+    pub fn update_get_jacobian_stateless(&self, state:base::Model2D<T>, increments:&[(T,T)])->Option<base::JacobianModel2D<T>>{
+        let new = self.update_coords_stateless(state,increments)?;
+        let mut data = base::JacobianModel2D::identity();
+        let _ = data.column(2,(state.y - new.y, new.x - state.x, T::one()));
+        Some(data)
+    }
+
+
+    /// Updates the pose with the steering angles and cumulative distances of the modules and
+    /// stores the readings
+    pub fn update_coords_odometry(&mut self, states:&[(T,T)])->Option<base::Model2D<T>>{
+        let increments:Vec<(T,T)> = states.iter().zip(self.odometry.iter()).map(|((angle,distance),previous)| (*angle,*distance - *previous)).collect();
+        self.x_t = self.update_coords_stateless(self.x_t,&increments)?;
+        self.update_odometry_readings(states);
+        Some(self.x_t)
+    }
+
+
+    /// Updates the pose with module states (steering angle, wheel speed) held for `time_step` ,
+    /// the stored readings are not used
+    pub fn update_coords_velocity(&mut self, states:&[(T,T)], time_step:T)->Option<base::Model2D<T>>{
+        let increments:Vec<(T,T)> = states.iter().map(|(angle,speed)| (*angle,*speed*time_step)).collect();
+        self.x_t = self.update_coords_stateless(self.x_t,&increments)?;
+        Some(self.x_t)
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn swerve_kinematics_test(){
+        let model = super::SwerveModel::rectangular(0.3f64,0.25).unwrap();
+        let states = model.module_states(0.4,-0.2,0.9);
+        let (vx,vy,omega) = model.body_twist(&states).unwrap();
+        assert!((vx-0.4).abs()<1e-12 && (vy+0.2).abs()<1e-12 && (omega-0.9).abs()<1e-12);
+
+        // a module pointing backwards and driving backwards is the same as forwards
+        let mut flipped = states.clone();
+        flipped[2] = (flipped[2].0 + std::f64::consts::PI,-flipped[2].1);
+        let (vx,vy,omega) = model.body_twist(&flipped).unwrap();
+        assert!((vx-0.4).abs()<1e-12 && (vy+0.2).abs()<1e-12 && (omega-0.9).abs()<1e-12);
+
+        // turning in place points the modules tangent to the circle through them
+        let states = model.module_states(0.0,0.0,1.0);
+        assert!((states[0].0-(0.3f64).atan2(-0.25)).abs()<1e-12);
+        assert!((states[0].1-0.3f64.hypot(0.25)).abs()<1e-12);
+
+        // the front modules faster than the rear ones is not a rigid motion , the fit averages them
+        let (vx,vy,omega) = model.body_twist(&[(0.0,1.1),(0.0,1.1),(0.0,0.9),(0.0,0.9)]).unwrap();
+        assert!((vx-1.0).abs()<1e-12 && vy.abs()<1e-12 && omega.abs()<1e-12);
+
+        assert!(model.body_twist(&[(0.0,1.0)]).is_none());
+        assert!(super::SwerveModel::new(vec![(0.1f64,0.1)]).is_none());
+        assert!(super::SwerveModel::new(vec![(0.1f64,0.1),(0.1,0.1)]).is_none());
+    }
+
+    #[test]
+    fn swerve_integration_test(){
+        // three modules , a constant twist drives on a circle
+        let mut model = super::SwerveModel::new(vec![(0.3f64,0.0),(-0.2,0.25),(-0.2,-0.25)]).unwrap();
+        model.x_t = crate::base::Model2D::new(1.0,-1.0,0.2);
+        let mut fine = model.clone();
+        let states = model.module_states(0.5,0.3,0.6);
+        let pos = model.update_coords_velocity(&states,1.0).unwrap();
+        for _ in 0..10000{
+            fine.update_coords_velocity(&states,1e-4);
+        }
+        assert!((pos.x-fine.x_t.x).abs()<1e-9 && (pos.y-fine.x_t.y).abs()<1e-9 && (pos.theta-fine.x_t.theta).abs()<1e-9);
+
+        // cumulative distances , driving sideways relative to the heading
+        let mut model = super::SwerveModel::rectangular(0.3f64,0.25).unwrap();
+        let left = std::f64::consts::FRAC_PI_2;
+        let pos = model.update_coords_odometry(&[(left,0.5);4]).unwrap();
+        assert!(pos.x.abs()<1e-12 && (pos.y-0.5).abs()<1e-12 && pos.theta.abs()<1e-12);
+        let pos = model.update_coords_odometry(&[(0.0,1.5);4]).unwrap();
+        assert!((pos.x-1.0).abs()<1e-12 && (pos.y-0.5).abs()<1e-12);
+    }
+
+    #[test]
+    fn jacobian_swerve_model_test(){
+        let model = super::SwerveModel::rectangular(0.3f64,0.25).unwrap();
+        let state = crate::base::Model2D::new(0.4,-0.3,1.1);
+        let increments = model.module_states(0.2,0.1,0.5);
+        let g = model.update_get_jacobian_stateless(state,&increments).unwrap();
+        let update = |p:[f64;3]| model.update_coords_stateless(crate::base::Model2D::new(p[0],p[1],p[2]),&increments).unwrap();
+        crate::tests::assert_numeric_jacobian(&g.data,update,[state.x,state.y,state.theta],1e-6,1e-8);
+    }
+}
@@ -1,20 +1,22 @@
 use crate::odometry_motion_model::OdometryModel;
 use crate::base::MotionUpdate2D;
+use crate::io::webots_log;
 use std::fs::File;
 use std::io::{Read,Write};
 use std::io::prelude::*;
-use std::io::BufRead;
 
 
 #[test]
 fn us_ws_model_test(){
     // file containing sample odometry data collected from webots 
     
-    let odom_data_n = file_read_odom_accurate(std::path::Path::new("sample_data/ws_pos8008.txt"), Path::new("sample_data/abs_pos8008.txt")).unwrap();
+    let odom_data_n = sample_log();
 
 
 
-    let odom_data  = get_raw_odometry_data().expect("Couldn't open file"); 
+    let odom_data  = webots_log::read_log("sample_data/test_us8008.txt")
+        .map(|records| webots_log::wheel_angles(&records))
+        .expect("Couldn't read log");
     let mut write_file = File::create("sample_data/your_values.txt").expect("Couldn't create");
 
     let mut abs_file = File::create("sample_data/x_y_t_abs_values.txt").expect("Couldn't create");
@@ -50,6 +52,51 @@ fn us_ws_model_test(){
     });
     write_file.flush().unwrap();
     abs_file.flush().unwrap();
+
+    // the ground truth has no heading , only the positions are compared
+    let estimate:Vec<crate::base::Model2D> = coords[1..].iter().map(|m| crate::base::Model2D::new(m.0,m.1,m.2)).collect();
+    let truth:Vec<crate::base::Model2D> = abs_coords.iter().map(|m| crate::base::Model2D::new(m.0,m.1,m.2)).collect();
+    let ate = crate::eval::absolute_trajectory_error(&estimate,&truth,false).unwrap();
+    let ate_aligned = crate::eval::absolute_trajectory_error(&estimate,&truth,true).unwrap();
+    // dead reckoning drifts by decimeters over the log
+    assert_eq!(ate.count,truth.len());
+    assert!(ate.rmse>0.0 && ate.rmse<0.5 && ate.max<1.0);
+    assert!(ate_aligned.rmse>0.0 && ate_aligned.rmse<0.25 && ate_aligned.max<0.5);
+    assert!(ate_aligned.rmse<=ate.rmse);
+}
+
+
+#[test]
+fn double_precision_model_test(){
+    // the same log in single and double precision should give (nearly) the same trajectory
+    let odom_data_n = sample_log();
+    let wheel_radius = 0.021;
+
+    let mut model_f32:OdometryModel<f32> = OdometryModel::new(0.1054);
+    let mut model_f64:OdometryModel<f64> = OdometryModel::new(0.1054);
+    let mut estimate_f32 = vec![crate::base::Model2D::new(0.0f32,0.0,1.57)];
+    let mut estimate_f64 = vec![crate::base::Model2D::new(0.0f64,0.0,1.57)];
+    let mut truth_f32:Vec<crate::base::Model2D<f32>> = Vec::new();
+    let mut truth_f64:Vec<crate::base::Model2D<f64>> = Vec::new();
+    for ((l,r),(x,y)) in odom_data_n.iter(){
+        let last = estimate_f32[estimate_f32.len()-1];
+        estimate_f32.push(model_f32.update_coords_odometry_stateless(last,l*wheel_radius,r*wheel_radius));
+        let last = estimate_f64[estimate_f64.len()-1];
+        estimate_f64.push(model_f64.update_coords_odometry_stateless(last,*l as f64*wheel_radius as f64,*r as f64*wheel_radius as f64));
+        truth_f32.push(crate::base::Model2D::new(*x,*y,1.57));
+        truth_f64.push(crate::base::Model2D::new(*x as f64,*y as f64,1.57));
+    }
+
+    let end_f32 = estimate_f32[estimate_f32.len()-1];
+    let end_f64 = estimate_f64[estimate_f64.len()-1];
+    assert!((end_f32.x as f64-end_f64.x).abs()<1e-2);
+    assert!((end_f32.y as f64-end_f64.y).abs()<1e-2);
+
+    let ate_f32 = crate::eval::absolute_trajectory_error(&estimate_f32[1..],&truth_f32,true).unwrap();
+    let ate_f64 = crate::eval::absolute_trajectory_error(&estimate_f64[1..],&truth_f64,true).unwrap();
+    // double precision is never worse than single precision , both stay within the dead reckoning drift
+    assert!(ate_f64.rmse<=ate_f32.rmse as f64 + 1e-6);
+    assert!(ate_f64.rmse<0.25 && ate_f64.max<0.5);
 }
 
 
@@ -64,10 +111,52 @@ fn us_ws_model_test(){
 
 
 
+#[test]
+fn integrators_sample_data_test(){
+    // exact arc , midpoint and euler integration of the same log
+    let odom_data_n = sample_log();
+    let wheel_radius = 0.021;
+    let integrators = [crate::base::Integrator::ExactArc,crate::base::Integrator::Midpoint,crate::base::Integrator::Euler];
+    let mut estimates:Vec<Vec<crate::base::Model2D<f64>>> = Vec::new();
+    for integrator in integrators.iter(){
+        let mut model:OdometryModel<f64> = OdometryModel::new(0.1054);
+        model.set_integrator(*integrator);
+        let mut estimate = vec![crate::base::Model2D::new(0.0,0.0,1.57)];
+        for ((l,r),_) in odom_data_n.iter(){
+            let last = estimate[estimate.len()-1];
+            estimate.push(model.update_coords_odometry_stateless(last,*l as f64*wheel_radius,*r as f64*wheel_radius));
+        }
+        estimates.push(estimate);
+    }
+    let max_distance = |a:&[crate::base::Model2D<f64>],b:&[crate::base::Model2D<f64>]| a.iter().zip(b.iter())
+        .map(|(p,q)| (p.x-q.x).hypot(p.y-q.y))
+        .fold(0.0,f64::max);
+    let midpoint = max_distance(&estimates[0],&estimates[1]);
+    let euler = max_distance(&estimates[0],&estimates[2]);
+    // the headings agree , the second order scheme stays within a fraction of a millimeter of the
+    // arc , the first order one drifts by about a centimeter over the log
+    assert!((estimates[0][estimates[0].len()-1].theta-estimates[2][estimates[2].len()-1].theta).abs()<1e-9);
+    assert!(midpoint<euler);
+    assert!(midpoint<0.1*euler);
+    assert!(midpoint<5e-4 && euler>5e-3 && euler<3e-2);
+
+    let truth:Vec<crate::base::Model2D<f64>> = odom_data_n.iter().map(|(_,(x,y))| crate::base::Model2D::new(*x as f64,*y as f64,1.57)).collect();
+    // the integration error is small next to the dead reckoning drift , all three track the
+    // ground truth equally well
+    let ates:Vec<_> = estimates.iter().map(|estimate| crate::eval::absolute_trajectory_error(&estimate[1..],&truth,true).unwrap()).collect();
+    for ate in ates.iter(){
+        assert!(ate.rmse<0.25 && ate.max<0.5);
+        assert!((ate.rmse-ates[0].rmse).abs()<1e-3);
+    }
+}
+
+
 #[test]
 fn odometry_model_test() {
     // file containing sample odometry data collected from webots 
-    let odom_data  = get_raw_odometry_data().expect("Couldn't open file"); 
+    let odom_data  = webots_log::read_log("sample_data/test_us8008.txt")
+        .map(|records| webots_log::wheel_angles(&records))
+        .expect("Couldn't read log");
     let mut write_file = File::create("sample_data/x_y_t_values.txt").expect("Couldn't create");
     
     let mut debug_file = File::create("sample_data/x_y_t_values_debug.txt").expect("Couldn't create");
@@ -94,113 +183,29 @@ fn odometry_model_test() {
 
 
 
-pub fn get_raw_odometry_data()->std::io::Result<Vec<(f32,f32)>>{
-    let mut readings = std::fs::File::open("sample_data/test8008.txt")?;
-    let mut readings_us = std::fs::File::open("sample_data/test_us8008.txt")?;
-    let mut file_data = String::new();
-    let mut file_data_us = String::new();
-    let mut reader = std::io::BufReader::new(readings);
-    let mut reader_us = std::io::BufReader::new(readings_us);
-    let mut data_anal:Vec<f32>=Vec::new();
-
-    let mut ws_data:Vec<f32>=Vec::new();
-    let mut us_data:Vec<f32>=Vec::new();
-    let mut laser_data:Vec<f32>=Vec::new();
-    let mut lines = reader_us.lines();
-    
-    let mut odometry_data = Vec::<(f32,f32)>::new();
-
-    while let Some(v) = lines.next(){
-        if let Ok(mut m) = v{
-            if let Ok(())=load_WS_to_array(&mut ws_data, &mut m){
-                odometry_data.push((ws_data[0],ws_data[1]))
-            }
-        }
-        ws_data.clear();
-    } 
-   println!("{:?}",odometry_data); 
-    Ok(odometry_data)
+/// Wheel angles and ground truth positions of the sample log. The supervisor positions are in a
+/// left handed frame , y is mirrored into the odometry frame
+fn sample_log()->Vec<((f32,f32),(f32,f32))>{
+    let angles = webots_log::read_columns::<2,_>("sample_data/ws_pos8008.txt").expect("Couldn't read log");
+    let positions = webots_log::read_columns::<2,_>("sample_data/abs_pos8008.txt").expect("Couldn't read log");
+    angles.iter().zip(positions.iter()).map(|(a,p)| ((a[0],a[1]),(p[0],-p[1]))).collect()
 }
 
-
-
-
-fn load_WS_to_array(array:&mut Vec<f32>, text:&mut String)->Result<(),()>{
-    let txt = text.clone();
-    let mut a = txt.split_once(':');
-    if let Some(p)=a{
-        if p.0=="WS"{
-            array.clear();
-            p.1.split("|").for_each(|m|{
-                if let Ok(f) = m.parse::<f32>(){
-                    array.push(f)
-                }
-            });
-            return Ok(())
-        }else{
-            return Err(())
+/// Checks a jacobian of a pose update against central differences of `update` around `at` ,
+/// column j is the derivative with respect to at[j]
+pub(crate) fn assert_numeric_jacobian<const N:usize>(jacobian:&[[f64;N];3], mut update:impl FnMut([f64;N])->crate::base::Model2D<f64>, at:[f64;N], h:f64, tolerance:f64){
+    for col in 0..N{
+        let (mut plus,mut minus) = (at,at);
+        plus[col] += h;
+        minus[col] -= h;
+        let (plus,minus) = (update(plus),update(minus));
+        let numeric = [(plus.x-minus.x)/(2.0*h),(plus.y-minus.y)/(2.0*h),(plus.theta-minus.theta)/(2.0*h)];
+        for (row,value) in numeric.iter().enumerate(){
+            assert!((jacobian[row][col]-value).abs()<tolerance,"at {:?} ({},{}) {} {}",at,row,col,jacobian[row][col],value);
         }
-    }else{
-        return Err(())
     }
 }
 
 
 
 
-use std::io::{BufReader};
-use std::path::Path;
-pub fn file_read_odom_accurate(path_odometry:&Path, path_abs:&Path)->std::io::Result<Vec<( (f32,f32),(f32,f32) )>>{
-    let mut odomfile = File::open(path_odometry)?;
-    let mut odom_reader  = BufReader::new(&mut odomfile);
-    let mut odom_str = String::new();
-
-    let mut absfile = File::open(path_abs)?;
-    let mut abs_reader  = BufReader::new(&mut absfile);
-    let mut abs_str = String::new();
-    
-    let mut output_vec = Vec::<((f32,f32),(f32,f32))>::new();
-
-    loop{
-        let mut datas = ((0.,0.),(0.,0.));
-        if let Ok(p) = odom_reader.read_line(&mut odom_str){
-            if p==0{
-                break;
-            }
-            odom_str = odom_str.replace("\r\n","");
-            //println!("ODOM STR {}",odom_str);
-            let mut odom_iter = odom_str.split("|");
-            let odom_l = odom_iter.next().unwrap().parse::<f32>().unwrap_or_else(|_|{0.0});
-            let odom_r = odom_iter.next().unwrap().parse::<f32>().unwrap_or_else(|_|{0.0});
-            datas.0.0=odom_l;
-            datas.0.1=odom_r;
-            //println!("ODOM {},{}",odom_l,odom_r);
-            odom_str.clear();
-        }else{
-            println!("EOF");
-            break;
-        }
-
-        if let Ok(p) = abs_reader.read_line(&mut abs_str){
-            if p==0{
-                break;
-            }
-            abs_str = abs_str.replace("\r\n","");
-            let mut abs_iter = abs_str.split("|");
-            let x = abs_iter.next().unwrap().parse::<f32>();
-            let y = abs_iter.next().unwrap().parse::<f32>();
-            datas.1.0=x.unwrap_or_else(|_|{0.0});
-            datas.1.1=y.unwrap_or_else(|_|{0.0});
-            //println!("ABS {},{}",datas.1.0,datas.1.1);
-            abs_str.clear();
-        }else{
-            break;
-        }
-        output_vec.push(datas);
-    }
-    
-    Ok(output_vec)
-}
-
-
-
@@ -0,0 +1,287 @@
+//! Unscented kalman filter localization (Probabilistic Robotics, Thrun et al. , table 3.4)
+//! The motion models are used as black boxes through
+//! [base::MotionUpdate2D::update_coords_odometry_stateless] , no analytic jacobians are needed.
+//! The wheel noise enters through an augmented state (x, y, theta, noise_l, noise_r).
+use crate::base;
+use crate::ekf::MeasurementModel2D;
+
+
+/// Dimension of the augmented state used in the prediction step
+const AUGMENTED:usize = 5;
+
+
+/// Unscented kalman filter over a [base::Model2D] pose
+pub struct UnscentedKalmanFilter<T:base::Real=f32>{
+    pub mean:base::Model2D<T>,
+    pub covariance:base::Covariance2D<T>,
+    alpha:T,
+    beta:T,
+    kappa:T
+}
+
+impl<T:base::Real> UnscentedKalmanFilter<T>{
+    /// Uses alpha = 1 , beta = 2 , kappa = 0. Smaller values of alpha give large negative weights
+    /// which single precision does not handle well
+    pub fn new(mean:base::Model2D<T>, covariance:base::Covariance2D<T>)->UnscentedKalmanFilter<T>{
+        Self::with_parameters(mean,covariance,T::one(),base::real(2.0),T::zero())
+    }
+
+    pub fn with_parameters(mean:base::Model2D<T>, covariance:base::Covariance2D<T>, alpha:T, beta:T, kappa:T)->UnscentedKalmanFilter<T>{
+        UnscentedKalmanFilter{
+            mean,
+            covariance,
+            alpha,
+            beta,
+            kappa
+        }
+    }
+
+
+    /// Returns the mean weight and covariance weight of the first sigma point, the weight of the
+    /// other sigma points and the spread of the sigma points for an n dimensional state
+    fn weights(&self, n:usize)->(T,T,T,T){
+        let n = base::real::<T>(n as f64);
+        let lambda = self.alpha*self.alpha*(n + self.kappa) - n;
+        let wm0 = lambda/(n + lambda);
+        let wc0 = wm0 + T::one() - self.alpha*self.alpha + self.beta;
+        let wi = base::real::<T>(0.5)/(n + lambda);
+        (wm0,wc0,wi,(n + lambda).sqrt())
+    }
+
+
+    /// Sigma points of a gaussian with the given mean and covariance
+    /// Returns None if the covariance is not positive definite
+    fn sigma_points<const N:usize>(&self, mean:[T;N], covariance:[[T;N];N])->Option<Vec<[T;N]>>{
+        let (_,_,_,spread) = self.weights(N);
+        let l = cholesky(covariance)?;
+        let mut points = vec![mean];
+        for sign in [T::one(),-T::one()].iter(){
+            for col in 0..N{
+                let mut point = mean;
+                for (value,l_row) in point.iter_mut().zip(l.iter()){
+                    *value += *sign*spread*l_row[col];
+                }
+                points.push(point);
+            }
+        }
+        Some(points)
+    }
+
+
+    /// Prediction step. `control_noise` is the covariance of the noise added to the readings
+    /// (odom_l, odom_r) , in their units : the wheel increments (dl, dr) in m^2 for the differential
+    /// drive models , the rear distance (m^2) and steering angle (rad^2) for the
+    /// [crate::ackermann_motion_model::AckermannModel] , the velocity ((m/s)^2) and steering angle
+    /// (rad^2) for the [crate::bicycle_motion_model::BicycleModel]. Same as
+    /// [crate::ekf::ExtendedKalmanFilter::predict].
+    /// Every sigma point is propagated through a clone of the model, the model itself is then
+    /// updated once with the noise free readings.
+    /// Returns Err if the covariance is not positive definite, the filter is left unchanged
+    #[allow(clippy::result_unit_err)]
+    pub fn predict<U:base::MotionUpdate2D<T>+Clone>(&mut self, model:&mut U, odom_l:T, odom_r:T, control_noise:&base::ControlCovariance2D<T>)->Result<(),()>{
+        let mut mean = [T::zero();AUGMENTED];
+        mean[0] = self.mean.x;
+        mean[1] = self.mean.y;
+        mean[2] = self.mean.theta;
+        let mut covariance = [[T::zero();AUGMENTED];AUGMENTED];
+        for (i,row) in covariance.iter_mut().enumerate().take(3){
+            for (j,value) in row.iter_mut().enumerate().take(3){
+                *value = self.covariance.get(i,j);
+            }
+        }
+        for i in 0..2{
+            for j in 0..2{
+                covariance[3+i][3+j] = control_noise.data[i][j];
+            }
+        }
+
+        let points = self.sigma_points(mean,covariance).ok_or(())?;
+        let mut propagated:Vec<base::Model2D<T>> = points.iter().skip(1).map(|p|{
+            let mut sigma_model = model.clone();
+            sigma_model.update_coords_odometry_stateless(base::Model2D::new(p[0],p[1],p[2]),odom_l+p[3],odom_r+p[4])
+        }).collect();
+        propagated.insert(0,model.update_coords_odometry_stateless(self.mean,odom_l,odom_r));
+
+        let (wm0,wc0,wi,_) = self.weights(AUGMENTED);
+        let (new_mean,new_covariance) = pose_mean_covariance(&propagated,wm0,wc0,wi);
+        self.mean = new_mean;
+        self.covariance = new_covariance;
+        Ok(())
+    }
+
+
+    /// Correction step with the given measurement, the jacobian of the measurement model is not
+    /// used. Returns Err if a covariance is singular, the filter is left unchanged
+    #[allow(clippy::result_unit_err)]
+    pub fn update<const M:usize,Z:MeasurementModel2D<M,T>>(&mut self, model:&Z, measurement:&[T;M])->Result<(),()>{
+        let mean = [self.mean.x,self.mean.y,self.mean.theta];
+        let points = self.sigma_points(mean,self.covariance.to_matrix().data).ok_or(())?;
+        let (_,wc0,wi,_) = self.weights(3);
+
+        let expected:Vec<[T;M]> = points.iter().map(|p| model.predict_measurement(base::Model2D::new(p[0],p[1],p[2]))).collect();
+        // the mean is taken over residuals to the first sigma point so angles are averaged correctly
+        let mut z_mean = expected[0];
+        for z in expected.iter().skip(1){
+            let r = model.residual(z,&expected[0]);
+            for (value,&r) in z_mean.iter_mut().zip(r.iter()){
+                *value += wi*r;
+            }
+        }
+
+        let mut s = model.get_noise();
+        let mut cross = [[T::zero();M];3];
+        for (i,(p,z)) in points.iter().zip(expected.iter()).enumerate(){
+            let w = if i==0 { wc0 } else { wi };
+            let r = model.residual(z,&z_mean);
+            let d = [p[0]-mean[0], p[1]-mean[1], base::normalize_angle(p[2]-mean[2])];
+            for a in 0..M{
+                for b in 0..M{
+                    s[a][b] += w*r[a]*r[b];
+                }
+            }
+            for (a,row) in cross.iter_mut().enumerate(){
+                for (b,value) in row.iter_mut().enumerate(){
+                    *value += w*d[a]*r[b];
+                }
+            }
+        }
+        let s_inv = crate::ekf::invert(s).ok_or(())?;
+
+        // K = Pxz*S^-1 (3xM)
+        let mut k = [[T::zero();M];3];
+        for (i,row) in k.iter_mut().enumerate(){
+            for (j,value) in row.iter_mut().enumerate(){
+                *value = (0..M).map(|l| cross[i][l]*s_inv[l][j]).sum();
+            }
+        }
+
+        let residual = model.residual(measurement,&z_mean);
+        let correction:Vec<T> = k.iter().map(|row| (0..M).map(|j| row[j]*residual[j]).sum()).collect();
+        self.mean = base::Model2D::new(self.mean.x + correction[0], self.mean.y + correction[1], self.mean.theta + correction[2]);
+
+        // P = P - K*S*K^T = P - K*Pxz^T
+        let mut kpxz = base::JacobianModel2D::zeros();
+        for (i,row) in kpxz.data.iter_mut().enumerate(){
+            for (j,value) in row.iter_mut().enumerate(){
+                *value = (0..M).map(|l| k[i][l]*cross[j][l]).sum();
+            }
+        }
+        self.covariance = base::Covariance2D::from_matrix(self.covariance.to_matrix() - kpxz);
+        Ok(())
+    }
+}
+
+
+
+/// Weighted mean and covariance of a set of poses, theta is averaged through the differences to
+/// the first pose so that angles around +-pi are handled
+fn pose_mean_covariance<T:base::Real>(poses:&[base::Model2D<T>], wm0:T, wc0:T, wi:T)->(base::Model2D<T>,base::Covariance2D<T>){
+    let weight = |i:usize, w0:T| if i==0 { w0 } else { wi };
+    let reference = poses[0].theta;
+    let mut mean = base::Model2D::new(T::zero(),T::zero(),reference);
+    for (i,p) in poses.iter().enumerate(){
+        let w = weight(i,wm0);
+        mean.x += w*p.x;
+        mean.y += w*p.y;
+        mean.theta += w*base::normalize_angle(p.theta - reference);
+    }
+
+    let mut covariance = base::JacobianModel2D::zeros();
+    for (i,p) in poses.iter().enumerate(){
+        let w = weight(i,wc0);
+        let d = [p.x - mean.x, p.y - mean.y, base::normalize_angle(p.theta - mean.theta)];
+        for (a,row) in covariance.data.iter_mut().enumerate(){
+            for (b,value) in row.iter_mut().enumerate(){
+                *value += w*d[a]*d[b];
+            }
+        }
+    }
+    (mean,base::Covariance2D::from_matrix(covariance))
+}
+
+
+
+/// Lower triangular cholesky factor L of a symmetric positive definite matrix (A = L*L^T)
+/// Returns None if the matrix is not positive definite
+fn cholesky<const N:usize,T:base::Real>(matrix:[[T;N];N])->Option<[[T;N];N]>{
+    let mut l = [[T::zero();N];N];
+    for i in 0..N{
+        for j in 0..=i{
+            let sum:T = (0..j).map(|k| l[i][k]*l[j][k]).sum();
+            if i==j{
+                let diagonal = matrix[i][i] - sum;
+                if diagonal < T::zero(){
+                    return None
+                }
+                l[i][j] = diagonal.sqrt();
+            }else if l[j][j] > T::zero(){
+                l[i][j] = (matrix[i][j] - sum)/l[j][j];
+            }
+        }
+    }
+    Some(l)
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::base::MotionUpdate2D;
+
+    #[test]
+    fn cholesky_test(){
+        let l = super::cholesky([[4.0f32,2.0],[2.0,3.0]]).unwrap();
+        assert!((l[0][0]-2.0).abs()<1e-6 && (l[1][0]-1.0).abs()<1e-6 && (l[1][1]-2f32.sqrt()).abs()<1e-6);
+        assert!(super::cholesky([[1.0f32,2.0],[2.0,1.0]]).is_none());
+    }
+
+    fn cross_check<T:MotionUpdate2D+Clone>(mut ekf_model:T, mut ukf_model:T, readings:&[(f32,f32)]){
+        let start = crate::base::Model2D::new(0.2,-0.1,0.3);
+        let covariance = crate::base::Covariance2D::diagonal(1e-4,1e-4,1e-4);
+        let control_noise = crate::base::ControlCovariance2D::diagonal(1e-5,1e-5);
+        let mut ekf = crate::ekf::ExtendedKalmanFilter::new(start,covariance);
+        let mut ukf = super::UnscentedKalmanFilter::new(start,covariance);
+
+        for (l,r) in readings.iter(){
+            ekf.predict(&mut ekf_model,*l,*r,&control_noise);
+            ukf.predict(&mut ukf_model,*l,*r,&control_noise).unwrap();
+            assert!((ekf.mean.x-ukf.mean.x).abs()<1e-3);
+            assert!((ekf.mean.y-ukf.mean.y).abs()<1e-3);
+            assert!((ekf.mean.theta-ukf.mean.theta).abs()<1e-3);
+            for i in 0..3{
+                for j in 0..3{
+                    let scale = ekf.covariance.get(i,i).max(ekf.covariance.get(j,j));
+                    assert!((ekf.covariance.get(i,j)-ukf.covariance.get(i,j)).abs()<0.05*scale,
+                        "({},{}) ekf {} ukf {}",i,j,ekf.covariance.get(i,j),ukf.covariance.get(i,j));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ukf_odometry_cross_check_test(){
+        // readings that are exact in binary , so the straight line is taken as a straight line
+        let readings = [(0.125,0.25),(0.25,0.375),(0.25,0.5),(0.5,0.625)];
+        cross_check(crate::odometry_motion_model::OdometryModel::new(0.1),crate::odometry_motion_model::OdometryModel::new(0.1),&readings);
+    }
+
+    #[test]
+    fn ukf_velocity_cross_check_test(){
+        // the velocity model takes increments , the straight line branch included
+        let readings = [(0.125,0.25),(0.125,0.125),(0.0625,0.03125)];
+        cross_check(crate::velocity_motion_model::VelocityMotionModel::new(0.1,0.5),crate::velocity_motion_model::VelocityMotionModel::new(0.1,0.5),&readings);
+    }
+
+    #[test]
+    fn ukf_update_test(){
+        let mut ukf = super::UnscentedKalmanFilter::new(
+            crate::base::Model2D::new(1.0,1.0,0.0),
+            crate::base::Covariance2D::diagonal(0.1,0.1,0.1));
+        let sensor = crate::ekf::RangeBearingMeasurement::new(3.0,1.0,0.01,0.01);
+        // true pose is (1.2, 1.0, 0.0)
+        ukf.update(&sensor,&[1.8,0.0]).unwrap();
+        assert!(ukf.mean.x>1.0);
+        assert!(ukf.covariance.get(0,0)<0.1);
+    }
+}